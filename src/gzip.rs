@@ -0,0 +1,329 @@
+//! Gzip decoding for Medium pages. reqwest's decompression features need
+//! async-compression, so the body is inflated here after it was read.
+
+/// Why a gzip body could not be decoded.
+#[derive(Debug)]
+pub(crate) enum GzipError {
+    /// Not gzip, or a corrupt stream.
+    Invalid,
+    /// Inflating would exceed the size limit.
+    TooLarge,
+    /// The input ended early, with what was inflated up to there.
+    Truncated(Vec<u8>),
+}
+
+const MAGIC: &[u8] = b"\x1f\x8b";
+const DEFLATE: u8 = 8;
+const FHCRC: u8 = 0x02;
+const FEXTRA: u8 = 0x04;
+const FNAME: u8 = 0x08;
+const FCOMMENT: u8 = 0x10;
+
+/// Whether `body` starts like a gzip stream.
+pub(crate) fn is_gzip(body: &[u8]) -> bool {
+    body.starts_with(MAGIC)
+}
+
+/// Decode one gzip member, failing with [`GzipError::TooLarge`] past `limit` bytes of
+/// output.
+pub(crate) fn decode(data: &[u8], limit: usize) -> Result<Vec<u8>, GzipError> {
+    let start = header_len(data)?;
+    let mut out = vec![];
+    let end = match inflate(&data[start..], &mut out, limit) {
+        Ok(used) => start + used,
+        Err(Fail::Eof) => return Err(GzipError::Truncated(out)),
+        Err(Fail::Invalid) => return Err(GzipError::Invalid),
+        Err(Fail::TooLarge) => return Err(GzipError::TooLarge),
+    };
+    // the trailer follows the last block: CRC-32 and length of the output, little endian
+    let Some(trailer) = data.get(end..end + 8) else {
+        return Err(GzipError::Truncated(out));
+    };
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let len = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+    match crc == crc32(&out) && len == out.len() as u32 {
+        true => Ok(out),
+        false => Err(GzipError::Invalid),
+    }
+}
+
+fn header_len(data: &[u8]) -> Result<usize, GzipError> {
+    let truncated = || GzipError::Truncated(vec![]);
+    if data.len() < 10 {
+        return Err(truncated());
+    }
+    if !is_gzip(data) || data[2] != DEFLATE {
+        return Err(GzipError::Invalid);
+    }
+    let flags = data[3];
+    let mut pos = 10;
+    if flags & FEXTRA != 0 {
+        let len = data.get(pos..pos + 2).ok_or_else(truncated)?;
+        pos += 2 + u16::from_le_bytes([len[0], len[1]]) as usize;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            let end = data
+                .get(pos..)
+                .and_then(|rest| rest.iter().position(|&b| b == 0))
+                .ok_or_else(truncated)?;
+            pos += end + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        pos += 2;
+    }
+    match pos <= data.len() {
+        true => Ok(pos),
+        false => Err(truncated()),
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+enum Fail {
+    Eof,
+    Invalid,
+    TooLarge,
+}
+
+/// Reads the deflate stream least significant bit first.
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buf: u32,
+    count: u32,
+}
+
+impl Bits<'_> {
+    fn take(&mut self, n: u32) -> Result<u32, Fail> {
+        while self.count < n {
+            let byte = *self.data.get(self.pos).ok_or(Fail::Eof)?;
+            self.pos += 1;
+            self.buf |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+        let value = self.buf & ((1 << n) - 1);
+        self.buf >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    /// Drop the bits left of the current byte, before a stored block.
+    fn align(&mut self) {
+        self.buf = 0;
+        self.count = 0;
+    }
+}
+
+const MAX_BITS: usize = 15;
+
+/// A canonical Huffman code, as the number of codes of each length and the symbols in
+/// code order.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for len in 1..=MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; offsets[MAX_BITS + 1] as usize];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Self { counts, symbols }
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, Fail> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..=MAX_BITS {
+            code |= bits.take(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return self
+                    .symbols
+                    .get((index + code - first) as usize)
+                    .copied()
+                    .ok_or(Fail::Invalid);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(Fail::Invalid)
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// Order the code length code lengths of a dynamic block are sent in.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Inflate a deflate stream into `out`, returning how many bytes of `data` it took.
+fn inflate(data: &[u8], out: &mut Vec<u8>, limit: usize) -> Result<usize, Fail> {
+    let mut bits = Bits {
+        data,
+        pos: 0,
+        buf: 0,
+        count: 0,
+    };
+    loop {
+        let last = bits.take(1)? == 1;
+        match bits.take(2)? {
+            0 => stored(&mut bits, out, limit)?,
+            1 => {
+                let (lit, dist) = fixed();
+                codes(&mut bits, out, limit, &lit, &dist)?
+            }
+            2 => {
+                let (lit, dist) = dynamic(&mut bits)?;
+                codes(&mut bits, out, limit, &lit, &dist)?
+            }
+            _ => return Err(Fail::Invalid),
+        }
+        if last {
+            return Ok(bits.pos);
+        }
+    }
+}
+
+fn stored(bits: &mut Bits, out: &mut Vec<u8>, limit: usize) -> Result<(), Fail> {
+    bits.align();
+    let header = bits.data.get(bits.pos..bits.pos + 4).ok_or(Fail::Eof)?;
+    let len = u16::from_le_bytes([header[0], header[1]]);
+    if len != !u16::from_le_bytes([header[2], header[3]]) {
+        return Err(Fail::Invalid);
+    }
+    bits.pos += 4;
+    let end = bits.pos + len as usize;
+    let available = &bits.data[bits.pos..end.min(bits.data.len())];
+    if out.len() + available.len() > limit {
+        return Err(Fail::TooLarge);
+    }
+    out.extend_from_slice(available);
+    bits.pos = end;
+    match end <= bits.data.len() {
+        true => Ok(()),
+        false => Err(Fail::Eof),
+    }
+}
+
+fn fixed() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic(bits: &mut Bits) -> Result<(Huffman, Huffman), Fail> {
+    let lit_count = bits.take(5)? as usize + 257;
+    let dist_count = bits.take(5)? as usize + 1;
+    let code_count = bits.take(4)? as usize + 4;
+    if lit_count > 286 || dist_count > 30 {
+        return Err(Fail::Invalid);
+    }
+    let mut code_lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_count] {
+        code_lengths[index] = bits.take(3)? as u8;
+    }
+    let code = Huffman::new(&code_lengths);
+    let mut lengths = vec![];
+    while lengths.len() < lit_count + dist_count {
+        let (len, repeat) = match code.decode(bits)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths.last().ok_or(Fail::Invalid)?;
+                (previous, 3 + bits.take(2)?)
+            }
+            17 => (0, 3 + bits.take(3)?),
+            _ => (0, 11 + bits.take(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(len, repeat as usize));
+    }
+    if lengths.len() != lit_count + dist_count || lengths[256] == 0 {
+        return Err(Fail::Invalid);
+    }
+    Ok((
+        Huffman::new(&lengths[..lit_count]),
+        Huffman::new(&lengths[lit_count..]),
+    ))
+}
+
+fn codes(
+    bits: &mut Bits,
+    out: &mut Vec<u8>,
+    limit: usize,
+    lit: &Huffman,
+    dist: &Huffman,
+) -> Result<(), Fail> {
+    loop {
+        let symbol = lit.decode(bits)? as usize;
+        if symbol == 256 {
+            return Ok(());
+        }
+        if out.len() >= limit {
+            return Err(Fail::TooLarge);
+        }
+        if symbol < 256 {
+            out.push(symbol as u8);
+            continue;
+        }
+        let index = symbol - 257;
+        let (Some(&base), Some(&extra)) = (LENGTH_BASE.get(index), LENGTH_EXTRA.get(index)) else {
+            return Err(Fail::Invalid);
+        };
+        let len = base as usize + bits.take(extra as u32)? as usize;
+        let index = dist.decode(bits)? as usize;
+        let (Some(&base), Some(&extra)) = (DIST_BASE.get(index), DIST_EXTRA.get(index)) else {
+            return Err(Fail::Invalid);
+        };
+        let distance = base as usize + bits.take(extra as u32)? as usize;
+        let Some(start) = out.len().checked_sub(distance) else {
+            return Err(Fail::Invalid);
+        };
+        if out.len() + len > limit {
+            return Err(Fail::TooLarge);
+        }
+        // the copy may overlap what it writes, so go byte by byte
+        for i in 0..len {
+            out.push(out[start + i]);
+        }
+    }
+}
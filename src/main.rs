@@ -0,0 +1,565 @@
+use std::{
+    collections::HashMap,
+    env,
+    io::{self, Read, Write},
+    path::PathBuf,
+    process,
+    sync::Arc,
+    time::Duration,
+};
+
+use summary_medium_post::{
+    build_request_preview, compare_agents, extract_article_links, list_models, summary_file_stem,
+    write_output, AISummary, AgentKind, CancelFlag, Claude3agent, ComparedSummary, CostTotal, Data,
+    ExtractOptions, MediumClient, OutputEncoding, ProgressEvent, ProgressStage, PublishedSince,
+    Quota, StreamEvent, SummaryFormat, SummaryOutput,
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+const USAGE: &str = "usage: summary_medium_post <url>... [--format text|json] [--dry-run] [--skip-code] [--paragraphs <n>] [--output <path>] [--stream] [--compare <agent>,<agent>] [--responses <n>] [--progress] [--summary-format numbered|json|plain]
+       summary_medium_post --links-from <list page url> [--since <date> | --max-age <days>] [--exclude-undated] [<option>...]
+       summary_medium_post --stdin [--format text|json] [--dry-run] [--output <path>] [--stream] [--compare <agent>,<agent>] [--summary-format numbered|json|plain]
+       summary_medium_post --list-models openai|ollama";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Debug)]
+struct Args {
+    urls: Vec<String>,
+    format: Format,
+    dry_run: bool,
+    skip_code: bool,
+    paragraphs: Option<usize>,
+    output: Option<PathBuf>,
+    stream: bool,
+    /// Print the models of this backend instead of summarizing.
+    list_models: Option<AgentKind>,
+    /// Summarize with both agents and print their summaries one after the other.
+    compare: Option<(AgentKind, AgentKind)>,
+    /// Also summarize the n most clapped reader responses.
+    responses: Option<usize>,
+    /// Print `[i/n] <url>` to stderr before each url.
+    progress: bool,
+    /// Summarize the text on stdin instead of fetching urls.
+    stdin: bool,
+    /// Reshape the summary into a numbered list, JSON array or plain lines.
+    summary_format: SummaryFormat,
+    /// Also summarize the stories linked from this tag, publication or profile page.
+    links_from: Option<String>,
+    /// Only take linked stories published since then.
+    since: Option<OffsetDateTime>,
+    /// Drop linked stories without a known publish date under `--since`.
+    exclude_undated: bool,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut urls = vec![];
+        let mut format = Format::Text;
+        let mut dry_run = false;
+        let mut skip_code = false;
+        let mut paragraphs = None;
+        let mut output = None;
+        let mut stream = false;
+        let mut list_models = None;
+        let mut compare = None;
+        let mut responses = None;
+        let mut progress = false;
+        let mut stdin = false;
+        let mut summary_format = SummaryFormat::default();
+        let mut links_from = None;
+        let mut since = None;
+        let mut max_age = None;
+        let mut exclude_undated = false;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--format" => {
+                    format = match args.next().as_deref() {
+                        Some("text") => Format::Text,
+                        Some("json") => Format::Json,
+                        other => return Err(format!("unknown format: {other:?}")),
+                    }
+                }
+                "--dry-run" => dry_run = true,
+                "--skip-code" => skip_code = true,
+                "--stream" => stream = true,
+                "--progress" => progress = true,
+                "--stdin" => stdin = true,
+                "--exclude-undated" => exclude_undated = true,
+                "--links-from" => {
+                    links_from = Some(args.next().ok_or("--links-from needs a url")?);
+                }
+                "--since" => {
+                    let date = args.next().ok_or("--since needs a date")?;
+                    since = Some(parse_date(&date)?);
+                }
+                "--max-age" => {
+                    let days = args.next().ok_or("--max-age needs a number of days")?;
+                    let days = days
+                        .parse::<u64>()
+                        .map_err(|_| format!("not a number: {days}"))?;
+                    max_age = Some(Duration::from_secs(days * 24 * 60 * 60));
+                }
+                "--paragraphs" => {
+                    let n = args.next().ok_or("--paragraphs needs a number")?;
+                    paragraphs = Some(n.parse().map_err(|_| format!("not a number: {n}"))?);
+                }
+                "--list-models" => {
+                    let kind = args.next().ok_or("--list-models needs an agent")?;
+                    list_models = Some(kind.parse::<AgentKind>().map_err(|err| err.to_string())?);
+                }
+                "--compare" => {
+                    let agents = args.next().ok_or("--compare needs two agents")?;
+                    let (first, second) = agents
+                        .split_once(',')
+                        .ok_or_else(|| format!("--compare needs two agents, got {agents}"))?;
+                    let parse = |kind: &str| {
+                        kind.trim()
+                            .parse::<AgentKind>()
+                            .map_err(|err| err.to_string())
+                    };
+                    compare = Some((parse(first)?, parse(second)?));
+                }
+                "--responses" => {
+                    let n = args.next().ok_or("--responses needs a number")?;
+                    responses = Some(n.parse().map_err(|_| format!("not a number: {n}"))?);
+                }
+                "--summary-format" => {
+                    let format = args.next().ok_or("--summary-format needs a format")?;
+                    summary_format = format
+                        .parse::<SummaryFormat>()
+                        .map_err(|err| err.to_string())?;
+                }
+                "--output" => {
+                    output = Some(PathBuf::from(args.next().ok_or("--output needs a path")?))
+                }
+                flag if flag.starts_with("--") => return Err(format!("unknown flag: {flag}")),
+                _ => urls.push(arg),
+            }
+        }
+        if stdin && !urls.is_empty() {
+            return Err("--stdin doesn't take urls".to_owned());
+        }
+        if stdin && (skip_code || paragraphs.is_some() || responses.is_some()) {
+            return Err(
+                "--skip-code, --paragraphs and --responses need a url, not --stdin".to_owned(),
+            );
+        }
+        if since.is_some() && max_age.is_some() {
+            return Err("--since and --max-age can't be combined".to_owned());
+        }
+        let since = since.or_else(|| max_age.map(|age| OffsetDateTime::now_utc() - age));
+        if (since.is_some() || exclude_undated) && links_from.is_none() {
+            return Err("--since, --max-age and --exclude-undated filter --links-from".to_owned());
+        }
+        if stdin && links_from.is_some() {
+            return Err("--stdin doesn't take urls".to_owned());
+        }
+        if urls.is_empty() && list_models.is_none() && !stdin && links_from.is_none() {
+            return Err("missing url".to_owned());
+        }
+        if stream && (format == Format::Json || output.is_some()) {
+            return Err("--stream only prints text to stdout".to_owned());
+        }
+        if compare.is_some() && (stream || output.is_some()) {
+            return Err("--compare only prints to stdout".to_owned());
+        }
+        if responses.is_some() && (stream || compare.is_some()) {
+            return Err("--responses can't be combined with --stream or --compare".to_owned());
+        }
+        if summary_format != SummaryFormat::AsIs && (stream || compare.is_some()) {
+            return Err("--summary-format can't be combined with --stream or --compare".to_owned());
+        }
+        // a bare array has no room for an article's subtitle or its responses
+        if summary_format == SummaryFormat::JsonArray && format == Format::Text && !stdin {
+            return Err("--summary-format json needs --format json for urls".to_owned());
+        }
+        Ok(Self {
+            urls,
+            format,
+            dry_run,
+            skip_code,
+            paragraphs,
+            output,
+            stream,
+            list_models,
+            compare,
+            responses,
+            progress,
+            stdin,
+            summary_format,
+            links_from,
+            since,
+            exclude_undated,
+        })
+    }
+
+    /// Where to write the summary of one article. A single url goes to the `--output`
+    /// path itself unless it names a directory; with several urls it must be a directory
+    /// and each article gets `<title slug>-<post id>.<txt|json>` inside it.
+    fn output_path(&self, title: &str, url: &str) -> Option<PathBuf> {
+        let output = self.output.as_ref()?;
+        let is_dir = self.urls.len() > 1
+            || output.is_dir()
+            || output.as_os_str().to_string_lossy().ends_with('/');
+        if !is_dir {
+            return Some(output.clone());
+        }
+        let name = summary_file_stem(title, url);
+        let extension = match self.format {
+            Format::Text => "txt",
+            Format::Json => "json",
+        };
+        Some(output.join(format!("{name}.{extension}")))
+    }
+}
+
+/// `2024-05-01` (midnight UTC) or an RFC 3339 timestamp.
+fn parse_date(date: &str) -> Result<OffsetDateTime, String> {
+    OffsetDateTime::parse(date, &Rfc3339)
+        .or_else(|_| OffsetDateTime::parse(&format!("{date}T00:00:00Z"), &Rfc3339))
+        .map_err(|_| format!("not a date: {date}"))
+}
+
+async fn run(mut args: Args) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(kind) = args.list_models {
+        for model in list_models(kind).await? {
+            println!("{model}");
+        }
+        return Ok(());
+    }
+    let mut agent = Claude3agent::new()?;
+    if let Some(quota) = Quota::from_env()? {
+        agent = agent.with_quota(Arc::new(quota));
+    }
+    if args.stdin {
+        return summarize_stdin(&args, &agent).await;
+    }
+    let mut client = MediumClient::from_env()?;
+    // pages already fetched while filtering a list, by url
+    let mut fetched = HashMap::new();
+    if let Some(list_url) = &args.links_from {
+        let list = client.fetch(list_url).await?;
+        let links = match args.since {
+            Some(since) => {
+                let filter = PublishedSince::new(since).include_unknown(!args.exclude_undated);
+                let stories = client.stories_published_since(&list, &filter).await?;
+                let mut links = vec![];
+                for story in stories {
+                    if let Some(page) = story.page {
+                        fetched.insert(story.url.clone(), page);
+                    }
+                    links.push(story.url);
+                }
+                links
+            }
+            None => extract_article_links(&list)?,
+        };
+        if links.is_empty() {
+            eprintln!("no stories to summarize on {list_url}");
+        }
+        args.urls.extend(links);
+    }
+    let stop = CancelFlag::new();
+    if args.urls.len() > 1 {
+        stop_on_ctrl_c(stop.clone());
+        client = client.with_cancel_flag(stop.clone());
+        agent = agent.with_cancel_flag(stop.clone());
+    }
+    let mut cost = CostTotal::default();
+    let total = args.urls.len();
+    for (i, url) in args.urls.iter().enumerate() {
+        if stop.is_cancelled() {
+            eprintln!("stopped, {i} of {total} urls done");
+            break;
+        }
+        let event = |stage, done, failed| ProgressEvent {
+            url,
+            stage,
+            done,
+            total,
+            succeeded: done - failed,
+            failed,
+        };
+        if args.progress {
+            print_progress(&event(ProgressStage::Started, i, 0));
+        }
+        let page = fetched.remove(url);
+        match summarize(&args, &client, &agent, url, page, &mut cost).await {
+            Ok(()) if args.progress => print_progress(&event(ProgressStage::Succeeded, i + 1, 0)),
+            Ok(()) => {}
+            Err(_) if stop.is_cancelled() => {
+                eprintln!("stopped, {i} of {total} urls done");
+                break;
+            }
+            Err(err) => {
+                if args.progress {
+                    print_progress(&event(ProgressStage::Failed, i + 1, 1));
+                }
+                return Err(err);
+            }
+        }
+    }
+    if args.urls.len() > 1 && cost.summaries > 0 {
+        eprintln!(
+            "estimated cost: ${:.4} for {} summaries",
+            cost.usd, cost.summaries
+        );
+        if cost.unpriced > 0 {
+            eprintln!(
+                "{} summaries used a model without a known price",
+                cost.unpriced
+            );
+        }
+    }
+    if stop.is_cancelled() {
+        process::exit(130);
+    }
+    Ok(())
+}
+
+/// `[i/n] <url>` when a url is started, and how it went with the counts so far when it
+/// is finished, in the shape of the library's batch [`ProgressEvent`]s.
+fn print_progress(event: &ProgressEvent<'_>) {
+    match event.stage {
+        ProgressStage::Started => eprintln!("[{}/{}] {}", event.done + 1, event.total, event.url),
+        stage => eprintln!(
+            "[{}/{}] {} {}: {} succeeded, {} failed, {} remaining",
+            event.done,
+            event.total,
+            match stage {
+                ProgressStage::Failed => "failed",
+                _ => "done",
+            },
+            event.url,
+            event.succeeded,
+            event.failed,
+            event.remaining()
+        ),
+    }
+}
+
+/// Cancel `flag` on the first Ctrl-C, so no further requests are sent: requests in
+/// flight still finish, and an article whose summary arrives is written before the run
+/// stops. A second Ctrl-C exits right away.
+fn stop_on_ctrl_c(flag: CancelFlag) {
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        flag.cancel();
+        eprintln!("finishing the current article, press Ctrl-C again to quit now");
+        if tokio::signal::ctrl_c().await.is_ok() {
+            process::exit(130);
+        }
+    });
+}
+
+async fn summarize(
+    args: &Args,
+    client: &MediumClient<'_>,
+    agent: &Claude3agent,
+    url: &str,
+    page: Option<Data>,
+    cost: &mut CostTotal,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let data = match page {
+        Some(data) => data,
+        None => client.fetch_article(url).await?,
+    };
+    let content = data.content_with(&ExtractOptions {
+        skip_code: args.skip_code,
+        take_paragraphs: args.paragraphs,
+        ..ExtractOptions::from_env()?
+    })?;
+
+    if args.dry_run {
+        println!("{}", build_request_preview(agent, content));
+        return Ok(());
+    }
+    client.check_content_length(url, &content)?;
+    if args.stream {
+        return stream_summary(agent, content).await;
+    }
+    if let Some((first, second)) = args.compare {
+        return print_comparison(args.format, first, second, &content).await;
+    }
+    let meta = data.meta().unwrap_or_default();
+    let res = agent.fetch(content.clone()).await?;
+    let responses_summary = match args.responses {
+        Some(limit) => {
+            let responses = client.fetch_responses(url, limit).await?;
+            match responses.is_empty() {
+                true => None,
+                false => Some(agent.summarize_responses(&responses).await?),
+            }
+        }
+        None => None,
+    };
+    let output = SummaryOutput::from_response(&content, &res)?
+        .with_url(url)
+        .with_subtitle(meta.subtitle)
+        .with_responses_summary(responses_summary)
+        .with_summary_format(args.summary_format);
+    cost.add(output.cost);
+    if output.truncated {
+        eprintln!("warning: the summary of {url} was cut off at the token limit");
+    }
+
+    let mut text = match (args.format, &output.subtitle) {
+        (Format::Text, Some(subtitle)) => format!("> {subtitle}\n\n{}", output.summary),
+        (Format::Text, None) => output.summary.clone(),
+        (Format::Json, _) => serde_json::to_string_pretty(&output)?,
+    };
+    if let (Format::Text, Some(responses)) = (args.format, &output.responses_summary) {
+        text.push_str(&format!("\n\nResponses:\n{responses}"));
+    }
+    match args.output_path(meta.title.as_deref().unwrap_or_default(), url) {
+        Some(path) => {
+            write_output(&path, &format!("{text}\n"), &OutputEncoding::default())
+                .map_err(|err| format!("cannot write {}: {err}", path.display()))?;
+            eprintln!("wrote {}", path.display());
+        }
+        None => println!("{text}"),
+    }
+    Ok(())
+}
+
+/// Summarize text piped in, for pipelines where another tool did the fetching.
+async fn summarize_stdin(
+    args: &Args,
+    agent: &Claude3agent,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut content = String::new();
+    io::stdin().read_to_string(&mut content)?;
+    if content.trim().is_empty() {
+        return Err("nothing to summarize on stdin".into());
+    }
+    if args.dry_run {
+        println!("{}", build_request_preview(agent, content));
+        return Ok(());
+    }
+    if args.stream {
+        return stream_summary(agent, content).await;
+    }
+    if let Some((first, second)) = args.compare {
+        return print_comparison(args.format, first, second, &content).await;
+    }
+    let res = agent.fetch(content.clone()).await?;
+    let output =
+        SummaryOutput::from_response(&content, &res)?.with_summary_format(args.summary_format);
+    if output.truncated {
+        eprintln!("warning: the summary was cut off at the token limit");
+    }
+    let text = match (args.format, &output.bullets) {
+        (Format::Text, Some(bullets)) => serde_json::to_string_pretty(bullets)?,
+        (Format::Text, None) => output.summary.clone(),
+        (Format::Json, _) => serde_json::to_string_pretty(&output)?,
+    };
+    match args.output_path("", "stdin") {
+        Some(path) => {
+            write_output(&path, &format!("{text}\n"), &OutputEncoding::default())
+                .map_err(|err| format!("cannot write {}: {err}", path.display()))?;
+            eprintln!("wrote {}", path.display());
+        }
+        None => println!("{text}"),
+    }
+    Ok(())
+}
+
+/// Summarize `content` with both agents and print the two sides.
+async fn print_comparison(
+    format: Format,
+    first: AgentKind,
+    second: AgentKind,
+    content: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let comparison = compare_agents(first, second, content).await?;
+    let sides = [(first, comparison.first), (second, comparison.second)];
+    match format {
+        Format::Text => {
+            for (kind, side) in &sides {
+                println!("{}\n", compared_text(*kind, side));
+            }
+        }
+        Format::Json => {
+            let sides = sides
+                .iter()
+                .map(|(kind, side)| {
+                    serde_json::json!({
+                        "agent": kind.to_string(),
+                        "model": side.model,
+                        "usage": side.usage,
+                        "summary": side.summary.as_ref().ok(),
+                        "error": side.summary.as_ref().err().map(|err| err.to_string()),
+                    })
+                })
+                .collect::<Vec<_>>();
+            println!("{}", serde_json::to_string_pretty(&sides)?);
+        }
+    }
+    Ok(())
+}
+
+/// `== claude (model, 812 in / 95 out tokens) ==` followed by the summary or the error.
+fn compared_text(kind: AgentKind, side: &ComparedSummary) -> String {
+    let mut heading = kind.to_string();
+    if let Some(model) = &side.model {
+        heading.push_str(&format!(" ({model}"));
+        if let Some(usage) = side.usage {
+            heading.push_str(&format!(
+                ", {} in / {} out tokens",
+                usage.input_tokens, usage.output_tokens
+            ));
+        }
+        heading.push(')');
+    }
+    let body = match &side.summary {
+        Ok(summary) => summary.clone(),
+        Err(err) => format!("error: {err}"),
+    };
+    format!("== {heading} ==\n{body}")
+}
+
+/// Print the summary as it is generated, flushing after every delta.
+async fn stream_summary(
+    agent: &Claude3agent,
+    content: String,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut stream = agent.fetch_stream(content).await?.max_reconnects(2);
+    let mut stdout = io::stdout().lock();
+    while let Some(event) = stream.next().await {
+        match event? {
+            StreamEvent::Delta(text) => {
+                stdout.write_all(text.as_bytes())?;
+                stdout.flush()?;
+            }
+            StreamEvent::Restarted => {
+                writeln!(stdout)?;
+                eprintln!("connection dropped, starting over");
+            }
+            StreamEvent::ToolUse(_) => {}
+        }
+    }
+    writeln!(stdout)?;
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            process::exit(2);
+        }
+    };
+    if let Err(err) = run(args).await {
+        eprintln!("error: {err}");
+        process::exit(1);
+    }
+}
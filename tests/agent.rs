@@ -0,0 +1,897 @@
+mod common;
+
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use common::{MockResponse, MockServer};
+use summary_medium_post::{
+    chunk_text, parse_retry_after_at, render_template, AISummary, AISummaryError, AgentKind,
+    AgentPool, ArticleResponse, AuditRecord, CancelFlag, ChunkOptions, Claude3agent,
+    Claude3respose, MediumClient, ModelRegistry, QualityRetry, Quota, RateLimiter, RetryPolicy,
+    Role, Sentiment, SentimentLabel, StreamEvent, SummaryCache, SummaryConfig, SummaryLength,
+    SummaryOverrides, SummaryStyle, Tone, ToolUseBlock,
+};
+
+#[test]
+fn priming_goes_before_the_article() {
+    let agent = Claude3agent::from_url("http://localhost/v1/messages", "key")
+        .unwrap()
+        .with_priming(vec![
+            (Role::User, "Sample article".to_owned()),
+            (Role::Assistant, "- sample point".to_owned()),
+        ]);
+
+    let body = agent.build_body("Real article".to_owned());
+
+    let messages = body["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 3);
+    assert_eq!(messages[0]["role"], "user");
+    assert_eq!(messages[0]["content"], "Sample article");
+    assert_eq!(messages[1]["role"], "assistant");
+    assert_eq!(messages[2]["role"], "user");
+    assert_eq!(messages[2]["content"], "Real article");
+}
+
+#[test]
+fn no_priming_by_default() {
+    let agent = Claude3agent::from_url("http://localhost/v1/messages", "key").unwrap();
+
+    let body = agent.build_body("Real article".to_owned());
+
+    assert_eq!(body["messages"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn length_sets_prompt_and_max_tokens() {
+    let agent = Claude3agent::from_url("http://localhost/v1/messages", "key").unwrap();
+    let default = agent.build_body("Real article".to_owned());
+    let short = agent
+        .with_length("short".parse().unwrap())
+        .build_body("Real article".to_owned());
+
+    assert_eq!(default["max_tokens"], 1024);
+    assert_eq!(short["max_tokens"], SummaryLength::Short.max_tokens());
+    assert!(short["system"].as_str().unwrap().contains("brief"));
+    assert!(!default["system"].as_str().unwrap().contains("brief"));
+}
+
+#[tokio::test]
+async fn ping_tells_rejected_key_from_unreachable_api() {
+    let reply =
+        r#"{"type":"error","error":{"type":"authentication_error","message":"invalid x-api-key"}}"#;
+    let server = MockServer::start(vec![MockResponse::new(401, reply)]).await;
+    let agent = Claude3agent::from_url(server.url("/v1/messages"), "bad").unwrap();
+
+    let err = agent.ping().await.unwrap_err();
+
+    assert!(
+        matches!(err, AISummaryError::Unauthorized { status: 401, ref message }
+        if message == "authentication_error: invalid x-api-key")
+    );
+    let body: serde_json::Value = serde_json::from_str(&server.requests()[0].body).unwrap();
+    assert_eq!(body["max_tokens"], 1);
+
+    let agent = Claude3agent::from_url("http://127.0.0.1:1/v1/messages", "key").unwrap();
+    assert!(matches!(
+        agent.ping().await,
+        Err(AISummaryError::FetchFailed(_))
+    ));
+}
+
+#[tokio::test]
+async fn ping_succeeds_with_valid_key() {
+    let reply = r#"{"id":"msg","type":"message","role":"assistant","model":"claude-3-haiku-20240307","content":[{"type":"text","text":"Hi"}],"stop_reason":"max_tokens","usage":{"input_tokens":8,"output_tokens":1}}"#;
+    let server = MockServer::start(vec![MockResponse::new(200, reply)]).await;
+    let agent = Claude3agent::from_url(server.url("/v1/messages"), "key").unwrap();
+
+    agent.ping().await.unwrap();
+}
+
+#[test]
+fn chunk_text_splits_on_words() {
+    assert_eq!(chunk_text("aaaa bbbb cccc", 2), ["aaaa", "bbbb", "cccc"]);
+    assert_eq!(chunk_text("aa bb cc dd", 2), ["aa bb cc", "dd"]);
+    assert!(chunk_text("  ", 2).is_empty());
+    // counted in chars, not bytes
+    assert_eq!(chunk_text("ééé ü ö", 1), ["ééé", "ü ö"]);
+}
+
+#[test]
+fn chunking_a_long_article_stays_linear() {
+    let text = "word ".repeat(400_000);
+
+    let chunks = chunk_text(&text, 50_000);
+
+    assert_eq!(chunks.len(), 10);
+    assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 200_000));
+}
+
+fn text_reply(text: &str) -> String {
+    serde_json::json!({"id": "msg", "type": "message", "role": "assistant", "model": "claude-3-haiku-20240307", "content": [{"type": "text", "text": text}], "stop_reason": "end_turn", "usage": {"input_tokens": 8, "output_tokens": 2}})
+        .to_string()
+}
+
+#[tokio::test]
+async fn poor_summary_is_retried_until_a_good_one() {
+    let good = "- Workspaces let cargo rebuild only the crates that changed\n\
+        - Lower debug info in dev builds to speed up linking\n\
+        - A faster linker like mold halves link times on big projects";
+    let server = MockServer::start(vec![
+        MockResponse::new(200, text_reply("- ok")),
+        MockResponse::new(200, text_reply(good)),
+    ])
+    .await;
+    let agent = Claude3agent::from_url(server.url("/v1/messages"), "key")
+        .unwrap()
+        .with_quality_retry(QualityRetry::new(3));
+
+    let res = agent.fetch("article".to_owned()).await.unwrap();
+
+    assert_eq!(res.summary().unwrap(), good);
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    let retried: serde_json::Value = serde_json::from_str(&requests[1].body).unwrap();
+    assert!(retried["system"]
+        .as_str()
+        .unwrap()
+        .contains("Your previous summary was too short"));
+}
+
+#[tokio::test]
+async fn quality_retry_stops_at_the_cap_with_the_best_attempt() {
+    let server = MockServer::start(vec![
+        MockResponse::new(200, text_reply("- ok")),
+        MockResponse::new(200, text_reply("- a longer but still short one")),
+        MockResponse::new(200, text_reply("- short")),
+    ])
+    .await;
+    let agent = Claude3agent::from_url(server.url("/v1/messages"), "key")
+        .unwrap()
+        .with_quality_retry(QualityRetry::new(2));
+
+    let res = agent.fetch("article".to_owned()).await.unwrap();
+
+    assert_eq!(res.summary().unwrap(), "- a longer but still short one");
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn chunked_summary_reports_dropped_chunks() {
+    let reply = r#"{"id":"msg","type":"message","role":"assistant","model":"claude-3-haiku-20240307","content":[{"type":"text","text":"- part"}],"stop_reason":"end_turn","usage":{"input_tokens":8,"output_tokens":2}}"#;
+    let server = MockServer::start(vec![MockResponse::new(200, reply)]).await;
+    let agent = Claude3agent::from_url(server.url("/v1/messages"), "key").unwrap();
+    let options = ChunkOptions {
+        chunk_tokens: 1,
+        max_chunks: 2,
+    };
+
+    let report = agent
+        .summarize_chunked("aa bb cc dd ee ff".to_owned(), options)
+        .await
+        .unwrap();
+
+    assert_eq!(report.summary, "- part");
+    assert_eq!(report.chunk_count, 2);
+    assert!(report.truncated);
+    assert_eq!(report.dropped_words, 4);
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    let combine: serde_json::Value = serde_json::from_str(&requests[2].body).unwrap();
+    assert_eq!(combine["messages"][0]["content"], "- part\n\n- part");
+}
+
+#[tokio::test]
+async fn cache_answers_repeated_requests() {
+    let reply = r#"{"id":"msg","type":"message","role":"assistant","model":"claude-3-haiku-20240307","content":[{"type":"text","text":"- cached"}],"stop_reason":"end_turn","usage":{"input_tokens":8,"output_tokens":2}}"#;
+    let server = MockServer::start(vec![MockResponse::new(200, reply)]).await;
+    let dir = std::env::temp_dir().join(format!("summary-cache-{}", std::process::id()));
+    let cache = SummaryCache::new(&dir);
+    let agent = Claude3agent::from_url(server.url("/v1/messages"), "key")
+        .unwrap()
+        .with_cache(cache.clone());
+
+    let first = agent.fetch("article".to_owned()).await.unwrap();
+    let second = agent.fetch("article".to_owned()).await.unwrap();
+    agent.fetch("other article".to_owned()).await.unwrap();
+
+    assert_eq!(first.summary().unwrap(), second.summary().unwrap());
+    assert_eq!(server.requests().len(), 2);
+
+    cache.clear().unwrap();
+    agent.fetch("article".to_owned()).await.unwrap();
+    assert_eq!(server.requests().len(), 3);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn stop_sequences_are_sent_when_set() {
+    let agent = Claude3agent::from_url("http://localhost/v1/messages", "key").unwrap();
+    assert!(agent
+        .build_body("a".to_owned())
+        .get("stop_sequences")
+        .is_none());
+
+    let agent = agent.with_stop_sequences(vec!["###".to_owned()]).unwrap();
+    assert_eq!(
+        agent.build_body("a".to_owned())["stop_sequences"],
+        serde_json::json!(["###"])
+    );
+
+    for sequences in [vec![" ".to_owned()], vec!["x".to_owned(); 9]] {
+        assert!(matches!(
+            agent.clone().with_stop_sequences(sequences),
+            Err(AISummaryError::InvalidStopSequences(_))
+        ));
+    }
+}
+
+#[tokio::test]
+async fn joins_text_blocks_and_reports_truncation() {
+    let reply = r#"{"id":"msg","type":"message","role":"assistant","model":"claude-3-haiku-20240307","content":[{"type":"text","text":"- one"},{"type":"tool_use","id":"t1","name":"save","input":{}},{"type":"text","text":"\n- tw"}],"stop_reason":"max_tokens","usage":{"input_tokens":8,"output_tokens":2}}"#;
+    let server = MockServer::start(vec![MockResponse::new(200, reply)]).await;
+    let agent = Claude3agent::from_url(server.url("/v1/messages"), "key").unwrap();
+
+    let res = agent.fetch("article".to_owned()).await.unwrap();
+
+    assert_eq!(res.summary().unwrap(), "- one\n- tw");
+    assert_eq!(res.stop_reason(), Some("max_tokens"));
+    assert!(res.is_truncated());
+}
+
+#[test]
+fn retry_after_takes_seconds_and_dates() {
+    let now = time::OffsetDateTime::parse(
+        "2015-10-21T07:27:00Z",
+        &time::format_description::well_known::Rfc3339,
+    )
+    .unwrap();
+    assert_eq!(parse_retry_after_at("7", now), Some(Duration::from_secs(7)));
+    assert_eq!(
+        parse_retry_after_at("Wed, 21 Oct 2015 07:28:00 GMT", now),
+        Some(Duration::from_secs(60))
+    );
+    assert_eq!(
+        parse_retry_after_at("Wed, 21 Oct 2015 07:00:00 GMT", now),
+        Some(Duration::ZERO)
+    );
+    assert_eq!(parse_retry_after_at("soon", now), None);
+}
+
+#[tokio::test]
+async fn rate_limited_request_waits_for_retry_after() {
+    let reply = r#"{"id":"msg","type":"message","role":"assistant","model":"claude-3-haiku-20240307","content":[{"type":"text","text":"- ok"}],"stop_reason":"end_turn","usage":{"input_tokens":8,"output_tokens":2}}"#;
+    let server = MockServer::start(vec![
+        MockResponse::new(429, "slow down").header("retry-after", "0"),
+        MockResponse::new(200, reply),
+    ])
+    .await;
+    // the backoff alone would outlast the timeout
+    let agent = Claude3agent::from_url(server.url("/v1/messages"), "key")
+        .unwrap()
+        .with_retry(RetryPolicy::new(1).initial_backoff(Duration::from_secs(60)));
+
+    let res = tokio::time::timeout(Duration::from_secs(5), agent.fetch("article".to_owned()))
+        .await
+        .expect("retry-after was not honored")
+        .unwrap();
+
+    assert_eq!(res.summary().unwrap(), "- ok");
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn pool_reuses_its_agents() {
+    let reply = r#"{"id":"msg","type":"message","role":"assistant","model":"claude-3-haiku-20240307","content":[{"type":"text","text":"- ok"}],"stop_reason":"end_turn","usage":{"input_tokens":8,"output_tokens":2}}"#;
+    let server = MockServer::start(vec![MockResponse::new(200, reply)]).await;
+    let mut built = 0;
+    let pool = AgentPool::new(2, || {
+        built += 1;
+        Claude3agent::from_url(server.url(&format!("/agent-{built}")), "key")
+    })
+    .unwrap();
+
+    for _ in 0..5 {
+        let res: Claude3respose = pool.summarize("article".to_owned()).await.unwrap();
+        assert_eq!(res.summary().unwrap(), "- ok");
+    }
+
+    assert_eq!(built, 2);
+    assert_eq!(pool.idle(), 2);
+    let requests = server.requests();
+    assert_eq!(requests.len(), 5);
+    assert!(requests
+        .iter()
+        .all(|request| request.path == "/agent-1" || request.path == "/agent-2"));
+}
+
+#[test]
+fn new_agent_needs_an_api_key() {
+    std::env::remove_var("CLAUDE_API");
+
+    assert!(matches!(
+        Claude3agent::new(),
+        Err(AISummaryError::NoAPIKey(_))
+    ));
+}
+
+#[tokio::test]
+async fn audit_records_each_request_with_the_key_masked() {
+    let reply = r#"{"id":"msg","type":"message","role":"assistant","model":"claude-3-haiku-20240307","content":[{"type":"text","text":"- ok"}],"stop_reason":"end_turn","usage":{"input_tokens":8,"output_tokens":2}}"#;
+    let server = MockServer::start(vec![MockResponse::new(200, reply)]).await;
+    let records = Arc::new(Mutex::new(vec![]));
+    let sink = records.clone();
+    let agent = Claude3agent::from_url(server.url("/v1/messages"), "sk-ant-secret-9f3c")
+        .unwrap()
+        .with_audit_sink(Arc::new(move |record: &AuditRecord| {
+            sink.lock().unwrap().push(record.clone())
+        }));
+
+    agent.fetch("first".to_owned()).await.unwrap();
+    agent.fetch("second".to_owned()).await.unwrap();
+
+    let records = records.lock().unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(server.requests().len(), 2);
+    for record in records.iter() {
+        assert_eq!(record.method, "POST");
+        assert_eq!(record.url, server.url("/v1/messages"));
+        let key = record.headers.iter().find(|(name, _)| name == "x-api-key");
+        assert_eq!(key.map(|(_, value)| value.as_str()), Some("****9f3c"));
+        assert!(!format!("{record:?}").contains("sk-ant-secret"));
+    }
+}
+
+#[test]
+fn rate_limiter_rejects_rates_that_are_not_positive() {
+    for rate in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+        assert!(matches!(
+            RateLimiter::new(rate),
+            Err(AISummaryError::InvalidRate(_))
+        ));
+    }
+    assert!(RateLimiter::new(2.5).is_ok());
+}
+
+#[tokio::test]
+async fn thai_article_is_summarized_in_thai_by_default() {
+    let server = MockServer::start(vec![MockResponse::bytes(200, fixture("thai.html"))]).await;
+    let content = MediumClient::new("sid=abc")
+        .unwrap()
+        .fetch(&server.url("/@somchai/rust-0123456789ab"))
+        .await
+        .unwrap()
+        .content()
+        .unwrap();
+    let agent = Claude3agent::from_url("http://localhost/v1/messages", "key").unwrap();
+
+    let body = agent.clone().build_body(content.clone());
+    let overridden = agent.with_language("english").build_body(content);
+
+    assert!(body["system"]
+        .as_str()
+        .unwrap()
+        .starts_with(&SummaryStyle::Bullets.instruction("thai")));
+    assert!(overridden["system"]
+        .as_str()
+        .unwrap()
+        .starts_with("The article is in thai, summarize it in english."));
+}
+
+#[tokio::test]
+async fn retry_after_past_the_cap_fails_with_the_rate_limit() {
+    let server = MockServer::start(vec![MockResponse::new(
+        429,
+        r#"{"type":"error","error":{"type":"rate_limit_error","message":"slow down"}}"#,
+    )
+    .header("retry-after", "3600")])
+    .await;
+    let agent = Claude3agent::from_url(server.url("/v1/messages"), "key")
+        .unwrap()
+        .with_retry(RetryPolicy::new(3).max_retry_after(Duration::from_secs(30)));
+
+    let err = tokio::time::timeout(Duration::from_secs(5), agent.fetch("article".to_owned()))
+        .await
+        .expect("slept through the retry-after")
+        .unwrap_err();
+
+    assert!(matches!(
+        err,
+        AISummaryError::ApiError { status: 429, retry_after: Some(wait), .. } if wait == Duration::from_secs(3600)
+    ));
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[test]
+fn prompt_notes_the_article_language_when_it_differs() {
+    let thai = "สวัสดีครับ นี่คือบทความ".to_owned();
+    let agent = Claude3agent::from_url("http://localhost/v1/messages", "key").unwrap();
+
+    let english = agent
+        .clone()
+        .with_language("english")
+        .build_body(thai.clone());
+    let same = agent.with_language("Thai").build_body(thai);
+
+    assert!(english["system"]
+        .as_str()
+        .unwrap()
+        .starts_with("The article is in thai, summarize it in english."));
+    assert!(!same["system"]
+        .as_str()
+        .unwrap()
+        .contains("The article is in"));
+}
+
+#[tokio::test]
+async fn fetch_with_overrides_one_call_only() {
+    let reply = r#"{"id":"msg","type":"message","role":"assistant","model":"claude-3-haiku-20240307","content":[{"type":"text","text":"Short."}],"stop_reason":"end_turn","usage":{"input_tokens":8,"output_tokens":2}}"#;
+    let server = MockServer::start(vec![MockResponse::new(200, reply)]).await;
+    let agent = Claude3agent::from_url(server.url("/v1/messages"), "key")
+        .unwrap()
+        .with_language("english")
+        .with_length(SummaryLength::Long);
+    let overrides = SummaryOverrides {
+        style: Some(SummaryStyle::TlDr),
+        ..SummaryOverrides::default()
+    };
+
+    agent
+        .fetch_with("article".to_owned(), &overrides)
+        .await
+        .unwrap();
+
+    let body: serde_json::Value = serde_json::from_str(&server.requests()[0].body).unwrap();
+    assert!(body["system"]
+        .as_str()
+        .unwrap()
+        .starts_with("can you give a one sentence TL;DR of this with english lang."));
+    assert_eq!(body["max_tokens"], 2048);
+    assert_eq!(agent.config().style, SummaryStyle::Bullets);
+}
+
+#[test]
+fn overrides_can_restore_defaults() {
+    let base = SummaryConfig {
+        language: Some("english".to_owned()),
+        style: SummaryStyle::TlDr,
+        tone: Tone::Critical,
+        ..SummaryConfig::default()
+    };
+    let overrides = SummaryOverrides {
+        language: Some(None),
+        style: Some(SummaryStyle::default()),
+        tone: Some(Tone::Neutral),
+        ..SummaryOverrides::default()
+    };
+
+    assert_eq!(overrides.apply(&base), SummaryConfig::default());
+    assert_eq!(SummaryOverrides::default().apply(&base), base);
+}
+
+#[tokio::test]
+async fn quota_is_shared_through_its_file() {
+    let reply = r#"{"id":"msg","type":"message","role":"assistant","model":"claude-3-haiku-20240307","content":[{"type":"text","text":"- ok"}],"stop_reason":"end_turn","usage":{"input_tokens":8,"output_tokens":2}}"#;
+    let server = MockServer::start(vec![MockResponse::new(200, reply)]).await;
+    let path = std::env::temp_dir().join(format!("summary-quota-{}", std::process::id()));
+    let quota = Quota::new(&path, 2, Duration::from_secs(3600));
+    quota.try_acquire().unwrap();
+    let agent = Claude3agent::from_url(server.url("/v1/messages"), "key")
+        .unwrap()
+        .with_quota(Arc::new(quota));
+
+    agent.fetch("article".to_owned()).await.unwrap();
+    let err = agent.fetch("article".to_owned()).await.unwrap_err();
+
+    assert!(matches!(
+        err,
+        AISummaryError::QuotaExceeded { limit: 2, retry_in, .. } if retry_in <= Duration::from_secs(3600)
+    ));
+    assert_eq!(server.requests().len(), 1);
+    // a later run reads the same file
+    assert_eq!(
+        Quota::new(&path, 2, Duration::from_secs(3600))
+            .used()
+            .unwrap(),
+        2
+    );
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn render_template_fills_known_placeholders() {
+    let values = [("title", "Rust".to_owned())];
+
+    assert_eq!(
+        render_template(
+            r#"About {title}, reply like {"points": []} {{title}}"#,
+            &values
+        )
+        .unwrap(),
+        r#"About Rust, reply like {"points": []} {title}"#
+    );
+    assert!(matches!(
+        render_template("In {lang}", &values),
+        Err(AISummaryError::UnknownPlaceholder(name)) if name == "lang"
+    ));
+}
+
+fn fixture(name: &str) -> Vec<u8> {
+    std::fs::read(
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name),
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn related_articles_share_one_conversation() {
+    let pages = MockServer::start(vec![
+        MockResponse::bytes(200, fixture("post.html.gz")).header("content-encoding", "gzip"),
+        MockResponse::bytes(200, fixture("cover.html")),
+    ])
+    .await;
+    let client = MediumClient::new("sid=abc").unwrap();
+    let mut articles = vec![];
+    for path in [
+        "/@me/ownership",
+        "/@janedoe/borrowing-without-tears-0123456789ab",
+    ] {
+        articles.push(
+            client
+                .fetch(&pages.url(path))
+                .await
+                .unwrap()
+                .content()
+                .unwrap(),
+        );
+    }
+    let reply = |text: &str| {
+        serde_json::json!({"id": "msg", "type": "message", "role": "assistant", "model": "claude-3-haiku-20240307", "content": [{"type": "text", "text": text}], "stop_reason": "end_turn", "usage": {"input_tokens": 8, "output_tokens": 2}})
+            .to_string()
+    };
+    let api = MockServer::start(vec![
+        MockResponse::new(200, reply("- ownership prevents data races")),
+        MockResponse::new(
+            200,
+            reply("- builds on the first: borrowing is checked at compile time"),
+        ),
+    ])
+    .await;
+    let agent = Claude3agent::from_url(api.url("/v1/messages"), "key").unwrap();
+
+    let summaries = agent.summarize_conversation(&articles).await.unwrap();
+
+    assert_eq!(
+        summaries,
+        [
+            "- ownership prevents data races",
+            "- builds on the first: borrowing is checked at compile time"
+        ]
+    );
+    let requests = api.requests();
+    assert_eq!(requests.len(), 2);
+    let second: serde_json::Value = serde_json::from_str(&requests[1].body).unwrap();
+    assert!(second["system"].as_str().unwrap().contains("related"));
+    let turns = second["messages"].as_array().unwrap();
+    assert_eq!(turns.len(), 3);
+    assert_eq!(turns[0]["content"], articles[0].as_str());
+    assert_eq!(turns[1]["role"], "assistant");
+    assert_eq!(turns[1]["content"], summaries[0].as_str());
+    assert_eq!(turns[2]["content"], articles[1].as_str());
+}
+
+#[tokio::test]
+async fn hybrid_bullets_are_backed_by_source_sentences() {
+    let reply = r#"{"id":"msg","type":"message","role":"assistant","model":"claude-3-haiku-20240307","content":[{"type":"text","text":"1. Workspaces cut rebuilds.\n2. Less debug info links faster.\n3. Mold halves link times."}],"stop_reason":"end_turn","usage":{"input_tokens":8,"output_tokens":9}}"#;
+    let server = MockServer::start(vec![MockResponse::new(200, reply)]).await;
+    let agent = Claude3agent::from_url(server.url("/v1/messages"), "key").unwrap();
+    let content = "Splitting a crate into a workspace means cargo rebuilds less. \
+        Lowering debug info in the dev profile makes builds link faster. \
+        A faster linker such as mold cuts link times in half.";
+
+    let bullets = agent.hybrid_summary(content, 3).await.unwrap();
+
+    assert_eq!(bullets.len(), 3);
+    for (bullet, source) in &bullets {
+        assert!(!bullet.is_empty());
+        assert!(
+            content.contains(source.as_str()),
+            "{source:?} is not in the source"
+        );
+    }
+    let sent: serde_json::Value = serde_json::from_str(&server.requests()[0].body).unwrap();
+    let numbered = sent["messages"][0]["content"].as_str().unwrap();
+    assert!(bullets
+        .iter()
+        .all(|(_, source)| numbered.contains(source.as_str())));
+}
+
+#[tokio::test]
+async fn sentiment_is_parsed_from_the_reply() {
+    let reply = r#"{"id":"msg","type":"message","role":"assistant","model":"claude-3-haiku-20240307","content":[{"type":"text","text":"Sure: {\"label\": \"negative\", \"score\": 0.85}"}],"stop_reason":"end_turn","usage":{"input_tokens":8,"output_tokens":9}}"#;
+    let server = MockServer::start(vec![MockResponse::new(200, reply)]).await;
+    let agent = Claude3agent::from_url(server.url("/v1/messages"), "key").unwrap();
+
+    let sentiment = agent.sentiment("article".to_owned()).await.unwrap();
+
+    assert_eq!(sentiment.label, SentimentLabel::Negative);
+    assert_eq!(sentiment.score, 0.85);
+    let mixed = Sentiment::parse(r#"{"label": "mixed", "score": 0.9}"#).unwrap();
+    assert_eq!(mixed.label, SentimentLabel::Neutral);
+    assert!(mixed.score < 0.5);
+    assert!(matches!(
+        Sentiment::parse("upbeat"),
+        Err(AISummaryError::InvalidSentiment(_))
+    ));
+}
+
+#[test]
+fn agent_model_defaults_from_the_registry() {
+    let registry = ModelRegistry::default();
+    let agent = Claude3agent::from_url("http://localhost/v1/messages", "key").unwrap();
+
+    let body = agent.build_body("article".to_owned());
+    assert_eq!(body["model"], registry.default_model(AgentKind::Claude));
+
+    let body = agent
+        .with_model("claude-3-5-sonnet-20240620")
+        .build_body("article".to_owned());
+    assert_eq!(body["model"], "claude-3-5-sonnet-20240620");
+
+    let mut registry = registry;
+    registry.set(AgentKind::Claude, "claude-3-opus-20240229");
+    assert_eq!(
+        registry.default_model(AgentKind::Claude),
+        "claude-3-opus-20240229"
+    );
+}
+
+#[test]
+fn registry_defaults_are_overridden_from_env() {
+    // no other test reads OLLAMA_MODEL
+    std::env::set_var("OLLAMA_MODEL", "qwen2.5:7b");
+
+    assert_eq!(
+        ModelRegistry::from_env().default_model(AgentKind::Ollama),
+        "qwen2.5:7b"
+    );
+    assert_eq!(
+        ModelRegistry::default().default_model(AgentKind::Ollama),
+        "llama3"
+    );
+}
+
+#[test]
+fn tone_instruction_is_in_the_system_prompt() {
+    let agent = Claude3agent::from_url("http://localhost/v1/messages", "key").unwrap();
+
+    let neutral = agent.build_body("article".to_owned());
+    let critical = agent
+        .with_tone(Tone::Critical)
+        .build_body("article".to_owned());
+
+    let neutral = neutral["system"].as_str().unwrap();
+    let critical = critical["system"].as_str().unwrap();
+    assert!(neutral.contains(Tone::Neutral.instruction()));
+    assert!(critical.contains(Tone::Critical.instruction()));
+    assert!(!critical.contains(Tone::Neutral.instruction()));
+}
+
+#[tokio::test]
+async fn system_prompt_template_is_filled_from_config() {
+    let agent = Claude3agent::from_url("http://127.0.0.1:1/v1/messages", "key")
+        .unwrap()
+        .with_language("german")
+        .with_style(SummaryStyle::TlDr)
+        .with_system_prompt("Summarize in {language} as {style}, at most {max_points} points.");
+
+    let body = agent.build_body("article".to_owned());
+
+    assert!(body["system"]
+        .as_str()
+        .unwrap()
+        .starts_with("Summarize in german as tldr, at most 5 points."));
+
+    let err = agent
+        .with_system_prompt("Summarize {article}")
+        .fetch("article".to_owned())
+        .await
+        .unwrap_err();
+    assert!(matches!(err, AISummaryError::UnknownPlaceholder(name) if name == "article"));
+}
+
+#[tokio::test]
+async fn responses_are_summarized_on_their_own() {
+    let reply = r#"{"id":"msg","type":"message","role":"assistant","model":"claude-3-haiku-20240307","content":[{"type":"text","text":"Readers liked it."}],"stop_reason":"end_turn","usage":{"input_tokens":8,"output_tokens":4}}"#;
+    let server = MockServer::start(vec![MockResponse::new(200, reply)]).await;
+    let agent = Claude3agent::from_url(server.url("/v1/messages"), "key")
+        .unwrap()
+        .with_language("thai");
+    let responses = [
+        ArticleResponse {
+            text: "Great post".to_owned(),
+            ..ArticleResponse::default()
+        },
+        ArticleResponse {
+            text: "Wrong about X".to_owned(),
+            ..ArticleResponse::default()
+        },
+    ];
+
+    let summary = agent.summarize_responses(&responses).await.unwrap();
+
+    assert_eq!(summary, "Readers liked it.");
+    let body: serde_json::Value = serde_json::from_str(&server.requests()[0].body).unwrap();
+    assert_eq!(
+        body["messages"][0]["content"],
+        "Great post\n\nWrong about X"
+    );
+    assert!(body["system"].as_str().unwrap().ends_with("Reply in thai."));
+    assert!(matches!(
+        agent.summarize_responses(&[]).await,
+        Err(AISummaryError::EmptyResponse)
+    ));
+}
+
+#[tokio::test]
+async fn claude_request_carries_api_headers() {
+    let reply = r#"{"id":"msg","type":"message","role":"assistant","model":"claude-3-haiku-20240307","content":[{"type":"text","text":"- ok"}],"stop_reason":"end_turn","usage":{"input_tokens":8,"output_tokens":2}}"#;
+    let server = MockServer::start(vec![MockResponse::new(200, reply)]).await;
+    let agent = Claude3agent::from_url(server.url("/v1/messages"), "sk-ant-key").unwrap();
+
+    agent.fetch("article".to_owned()).await.unwrap();
+    agent
+        .summarize_responses(&[ArticleResponse {
+            author: None,
+            text: "Nice".to_owned(),
+            claps: 1,
+        }])
+        .await
+        .unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    for request in requests {
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/v1/messages");
+        assert_eq!(request.header("x-api-key"), Some("sk-ant-key"));
+        assert_eq!(request.header("anthropic-version"), Some("2023-06-01"));
+        assert_eq!(request.header("content-type"), Some("application/json"));
+        assert_eq!(request.header("anthropic-beta"), None);
+    }
+}
+
+/// A server-sent event stream of the messages API with the given events.
+fn sse(events: &[&str]) -> String {
+    events
+        .iter()
+        .map(|data| format!("event: message\ndata: {data}\n\n"))
+        .collect()
+}
+
+const TEXT_START: &str =
+    r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#;
+const MESSAGE_STOP: &str = r#"{"type":"message_stop"}"#;
+
+fn text_delta(text: &str) -> String {
+    serde_json::json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": text}})
+        .to_string()
+}
+
+#[tokio::test]
+async fn dropped_stream_reconnects_and_completes() {
+    let server = MockServer::start(vec![
+        // ends before message_stop
+        MockResponse::new(200, sse(&[TEXT_START, &text_delta("- fir")])),
+        MockResponse::new(
+            200,
+            sse(&[
+                TEXT_START,
+                &text_delta("- first"),
+                &text_delta("\n- second"),
+                MESSAGE_STOP,
+            ]),
+        ),
+    ])
+    .await;
+    let agent = Claude3agent::from_url(server.url("/v1/messages"), "key").unwrap();
+    let mut stream = agent
+        .fetch_stream("article".to_owned())
+        .await
+        .unwrap()
+        .max_reconnects(1);
+
+    let mut events = vec![];
+    while let Some(event) = stream.next().await {
+        events.push(event.unwrap());
+    }
+
+    assert_eq!(
+        events,
+        [
+            StreamEvent::Delta("- fir".to_owned()),
+            StreamEvent::Restarted,
+            StreamEvent::Delta("- first".to_owned()),
+            StreamEvent::Delta("\n- second".to_owned()),
+        ]
+    );
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn cancelled_stream_does_not_reconnect() {
+    let server = MockServer::start(vec![MockResponse::new(
+        200,
+        sse(&[TEXT_START, &text_delta("- fir")]),
+    )])
+    .await;
+    let cancel = CancelFlag::new();
+    let agent = Claude3agent::from_url(server.url("/v1/messages"), "key")
+        .unwrap()
+        .with_cancel_flag(cancel.clone());
+    let mut stream = agent
+        .fetch_stream("article".to_owned())
+        .await
+        .unwrap()
+        .max_reconnects(3);
+
+    assert!(matches!(
+        stream.next().await,
+        Some(Ok(StreamEvent::Delta(_)))
+    ));
+    cancel.cancel();
+
+    assert!(matches!(
+        stream.next().await,
+        Some(Err(AISummaryError::Cancelled))
+    ));
+    assert!(stream.next().await.is_none());
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn interleaved_stream_keeps_text_and_tool_blocks_apart() {
+    let tool_start = r#"{"type":"content_block_start","index":1,"content_block":{"type":"tool_use","id":"toolu_1","name":"record_summary","input":{}}}"#;
+    let tool_delta = |partial: &str| {
+        serde_json::json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": partial}})
+            .to_string()
+    };
+    let body = sse(&[
+        r#"{"type":"message_start","message":{"id":"msg","role":"assistant"}}"#,
+        TEXT_START,
+        &text_delta("- first"),
+        tool_start,
+        &tool_delta(r#"{"points":"#),
+        &text_delta("\n- second"),
+        &tool_delta(r#" 2}"#),
+        r#"{"type":"content_block_stop","index":1}"#,
+        r#"{"type":"ping"}"#,
+        r#"{"type":"content_block_stop","index":0}"#,
+        MESSAGE_STOP,
+    ]);
+    let server = MockServer::start(vec![MockResponse::new(200, body)]).await;
+    let agent = Claude3agent::from_url(server.url("/v1/messages"), "key").unwrap();
+
+    let message = agent
+        .fetch_stream("article".to_owned())
+        .await
+        .unwrap()
+        .collect_message()
+        .await
+        .unwrap();
+
+    assert_eq!(message.text, "- first\n- second");
+    assert_eq!(
+        message.tool_uses,
+        [ToolUseBlock {
+            index: 1,
+            id: "toolu_1".to_owned(),
+            name: "record_summary".to_owned(),
+            input: serde_json::json!({"points": 2}),
+        }]
+    );
+}
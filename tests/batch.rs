@@ -0,0 +1,306 @@
+mod common;
+
+use std::{fs, sync::Mutex, time::Duration};
+
+use common::{MockResponse, MockServer};
+use summary_medium_post::{
+    summarize_many, summarize_many_pipelined, summarize_many_with_progress, AISummaryError,
+    CancelFlag, Claude3agent, ClientError, CostTotal, DynSummarizer, MediumClient, ProgressStage,
+    PublishedSince, SummaryFuture,
+};
+
+const PAGE: &str =
+    r#"<html><script>{"__typename":"Post","type":"P","text":"Hello"}</script></html>"#;
+
+/// The test pages are far below the default minimum length.
+fn client() -> MediumClient<'static> {
+    MediumClient::builder("sid=abc")
+        .min_content_chars(0)
+        .build()
+        .unwrap()
+}
+
+struct Echo;
+
+impl DynSummarizer for Echo {
+    fn summarize(&self, content: String) -> SummaryFuture<'_> {
+        Box::pin(async move { Ok(format!("- {content}")) })
+    }
+}
+
+#[tokio::test]
+async fn checkpoint_skips_finished_urls() {
+    let server = MockServer::start(vec![MockResponse::new(200, PAGE)]).await;
+    let client = client();
+    let dir = std::env::temp_dir().join(format!("summary-batch-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let checkpoint = dir.join("done.jsonl");
+    let first = server.url("/@me/first-0123456789ab");
+    let second = server.url("/@me/second-0123456789ac");
+    let invalid = server.url("/not-a-post");
+    // a line cut off by a crash
+    fs::write(&checkpoint, r#"{"url":"x","sum"#).unwrap();
+
+    let results = summarize_many(
+        &client,
+        &Echo,
+        &[first.clone(), invalid.clone()],
+        Some(&checkpoint),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(results[0].summary.as_deref().unwrap(), "- Hello");
+    assert!(results[1].summary.is_err());
+    assert_eq!(server.requests().len(), 1);
+
+    let results = summarize_many(&client, &Echo, &[first, second], Some(&checkpoint))
+        .await
+        .unwrap();
+
+    assert!(results[0].resumed);
+    assert!(!results[1].resumed);
+    assert_eq!(server.requests().len(), 2);
+    let lines = fs::read_to_string(&checkpoint).unwrap();
+    assert_eq!(lines.lines().count(), 3);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn progress_reports_every_url() {
+    let server = MockServer::start(vec![MockResponse::new(200, PAGE)]).await;
+    let client = client();
+    let urls = [
+        server.url("/@me/first-0123456789ab"),
+        server.url("/not-a-post"),
+    ];
+    let events = Mutex::new(vec![]);
+
+    summarize_many_with_progress(&client, &Echo, &urls, None, |event| {
+        events.lock().unwrap().push((
+            event.stage,
+            event.remaining(),
+            event.succeeded,
+            event.failed,
+        ));
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(
+        events.into_inner().unwrap(),
+        [
+            (ProgressStage::Started, 2, 0, 0),
+            (ProgressStage::Succeeded, 1, 1, 0),
+            (ProgressStage::Started, 1, 1, 0),
+            (ProgressStage::Failed, 0, 1, 1),
+        ]
+    );
+}
+
+/// Notes how many pages the server had served when each summary finished.
+struct Slow<'a> {
+    server: &'a MockServer,
+    served: Mutex<Vec<usize>>,
+}
+
+impl DynSummarizer for Slow<'_> {
+    fn summarize(&self, content: String) -> SummaryFuture<'_> {
+        Box::pin(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            self.served
+                .lock()
+                .unwrap()
+                .push(self.server.requests().len());
+            Ok(content)
+        })
+    }
+}
+
+#[tokio::test]
+async fn pipeline_fetches_ahead_while_summarizing() {
+    let server = MockServer::start(vec![MockResponse::new(200, PAGE)]).await;
+    let client = client();
+    let urls = (0..3)
+        .map(|i| server.url(&format!("/@me/post{i}-0123456789ab")))
+        .collect::<Vec<_>>();
+    let agent = Slow {
+        server: &server,
+        served: Mutex::new(vec![]),
+    };
+
+    let results = summarize_many_pipelined(&client, &agent, &urls, None, 2, |_| {})
+        .await
+        .unwrap();
+
+    let urls_done = results.iter().map(|r| r.url.as_str()).collect::<Vec<_>>();
+    assert_eq!(urls_done, urls);
+    assert!(results.iter().all(|r| r.summary.is_ok()));
+    // the later articles were fetched while the first one was being summarized
+    assert_eq!(agent.served.into_inner().unwrap(), [3, 3, 3]);
+}
+
+#[tokio::test]
+async fn short_articles_are_not_summarized() {
+    let server = MockServer::start(vec![MockResponse::new(200, PAGE)]).await;
+    let client = MediumClient::new("sid=abc").unwrap();
+    let url = server.url("/@me/stub-0123456789ab");
+
+    let results = summarize_many(&client, &Echo, std::slice::from_ref(&url), None)
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        &results[0].summary,
+        Err(AISummaryError::FetchFailed(ClientError::ContentTooShort { url: u, chars: 5, min: 200 })) if *u == url
+    ));
+}
+
+#[tokio::test]
+async fn batch_totals_the_cost_of_its_summaries() {
+    let pages = MockServer::start(vec![MockResponse::new(200, PAGE)]).await;
+    let api = MockServer::start(vec![MockResponse::new(
+        200,
+        r#"{"id":"msg","type":"message","role":"assistant","model":"claude-3-haiku-20240307","content":[{"type":"text","text":"- ok"}],"stop_reason":"end_turn","usage":{"input_tokens":1000000,"output_tokens":1000000}}"#,
+    )])
+    .await;
+    let agent = Claude3agent::from_url(api.url("/v1/messages"), "key").unwrap();
+    let urls = [
+        pages.url("/@me/first-0123456789ab"),
+        pages.url("/not-a-post"),
+        pages.url("/@me/second-0123456789ac"),
+    ];
+
+    let results = summarize_many(&client(), &agent, &urls, None)
+        .await
+        .unwrap();
+
+    assert_eq!(results[0].cost, Some(1.5));
+    assert_eq!(results[1].cost, None);
+    let total = CostTotal::of_batch(&results);
+    assert_eq!(total.usd, 3.0);
+    assert_eq!(total.summaries, 2);
+    assert_eq!(total.unpriced, 0);
+    // agents that don't price their summaries are counted as unpriced
+    let results = summarize_many(&client(), &Echo, &urls[..1], None)
+        .await
+        .unwrap();
+    assert_eq!(CostTotal::of_batch(&results).unpriced, 1);
+}
+
+/// Replaces the checkpoint file with a directory while summarizing, so it can't be
+/// written any more.
+struct BreaksCheckpoint(std::path::PathBuf);
+
+impl DynSummarizer for BreaksCheckpoint {
+    fn summarize(&self, content: String) -> SummaryFuture<'_> {
+        Box::pin(async move {
+            if self.0.is_file() {
+                fs::remove_file(&self.0).unwrap();
+                fs::create_dir(&self.0).unwrap();
+            }
+            Ok(content)
+        })
+    }
+}
+
+#[tokio::test]
+async fn failed_checkpoint_write_stops_the_batch() {
+    let server = MockServer::start(vec![MockResponse::new(200, PAGE)]).await;
+    let dir = std::env::temp_dir().join(format!("summary-broken-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let checkpoint = dir.join("done.jsonl");
+    let urls = (0..5)
+        .map(|i| server.url(&format!("/@me/post{i}-0123456789ab")))
+        .collect::<Vec<_>>();
+    let client = client();
+    let agent = BreaksCheckpoint(checkpoint.clone());
+
+    let batch = summarize_many_pipelined(&client, &agent, &urls, Some(&checkpoint), 1, |_| {});
+    let result = tokio::time::timeout(Duration::from_secs(5), batch)
+        .await
+        .expect("the batch returns instead of hanging");
+
+    assert!(matches!(result, Err(AISummaryError::Checkpoint(_))));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+/// Cancels `flag` while summarizing, like a Ctrl-C during the first article.
+struct CancelsWhileBusy(CancelFlag);
+
+impl DynSummarizer for CancelsWhileBusy {
+    fn summarize(&self, content: String) -> SummaryFuture<'_> {
+        Box::pin(async move {
+            self.0.cancel();
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Ok(content)
+        })
+    }
+}
+
+#[tokio::test]
+async fn cancelled_batch_keeps_the_summary_in_flight() {
+    let server = MockServer::start(vec![MockResponse::new(200, PAGE)]).await;
+    let cancel = CancelFlag::new();
+    let client = client().with_cancel_flag(cancel.clone());
+    let dir = std::env::temp_dir().join(format!("summary-cancel-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let checkpoint = dir.join("done.jsonl");
+    let urls = (0..4)
+        .map(|i| server.url(&format!("/@me/post{i}-0123456789ab")))
+        .collect::<Vec<_>>();
+
+    let results = summarize_many(&client, &CancelsWhileBusy(cancel), &urls, Some(&checkpoint))
+        .await
+        .unwrap();
+
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].summary.as_deref().unwrap(), "Hello");
+    assert_eq!(fs::read_to_string(&checkpoint).unwrap().lines().count(), 1);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn feed_skips_articles_older_than_max_age() {
+    let day = 24 * 60 * 60 * 1000;
+    let now = time::OffsetDateTime::now_utc().unix_timestamp() * 1000;
+    let story = |id: &str, age_days: i64| {
+        format!(
+            r#""Post:{id}":{{"__typename":"Post","firstPublishedAt":{},"mediumUrl":"https://medium.com/@me/post-{id}"}}"#,
+            now - age_days * day
+        )
+    };
+    let feed = format!(
+        r#"<html><a href="/@me/post-aaaaaaaaaaaa">1</a><a href="/@me/post-bbbbbbbbbbbb">2</a>
+<a href="/@me/post-cccccccccccc">3</a><a href="/@me/post-dddddddddddd">4</a>
+<script>{{{},{},{}}}</script></html>"#,
+        story("aaaaaaaaaaaa", 1),
+        story("bbbbbbbbbbbb", 40),
+        story("cccccccccccc", 6),
+    );
+    let server = MockServer::start(vec![
+        MockResponse::new(200, feed),
+        // the undated story, fetched for its date
+        MockResponse::new(200, PAGE),
+    ])
+    .await;
+    let client = client();
+    let feed = client.fetch(&server.url("/tag/rust")).await.unwrap();
+    let week = Duration::from_secs(7 * 24 * 60 * 60);
+
+    let recent = client
+        .links_published_since(&feed, &PublishedSince::max_age(week).include_unknown(false))
+        .await
+        .unwrap();
+    let results = summarize_many(&client, &Echo, &recent, None).await.unwrap();
+
+    let summarized = results.iter().map(|r| r.url.as_str()).collect::<Vec<_>>();
+    assert_eq!(
+        summarized,
+        [
+            server.url("/@me/post-aaaaaaaaaaaa"),
+            server.url("/@me/post-cccccccccccc")
+        ]
+    );
+    assert!(results.iter().all(|r| r.summary.is_ok()));
+}
@@ -0,0 +1,133 @@
+//! Replays recorded cassettes (a saved Medium page and a saved Anthropic response)
+//! through the real fetch, extract and summarize code paths. Run with
+//! `cargo test --features e2e-replay`.
+
+#![cfg(feature = "e2e-replay")]
+
+mod common;
+
+use std::path::Path;
+
+use common::{MockResponse, MockServer};
+use serde::Deserialize;
+use summary_medium_post::{
+    summarize_url_to_markdown_file_with_client, summarize_with_client, Claude3agent, MediumClient,
+};
+
+#[derive(Deserialize)]
+struct Cassette {
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Deserialize)]
+struct Interaction {
+    request: RecordedCall,
+    response: RecordedResponse,
+}
+
+#[derive(Deserialize)]
+struct RecordedCall {
+    method: String,
+    path: String,
+}
+
+#[derive(Deserialize)]
+struct RecordedResponse {
+    status: u16,
+    content_type: String,
+    body_file: String,
+}
+
+/// A server answering with the cassette's responses in order.
+async fn replay(name: &str) -> (MockServer, Cassette) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/cassettes")
+        .join(name);
+    let cassette: Cassette =
+        serde_json::from_str(&std::fs::read_to_string(dir.join("cassette.json")).unwrap()).unwrap();
+    let responses = cassette
+        .interactions
+        .iter()
+        .map(|interaction| {
+            let response = &interaction.response;
+            let body = std::fs::read_to_string(dir.join(&response.body_file)).unwrap();
+            MockResponse::new(response.status, body).header("content-type", &response.content_type)
+        })
+        .collect();
+    (MockServer::start(responses).await, cassette)
+}
+
+/// The requests sent are the ones recorded, in the same order.
+fn assert_replayed(server: &MockServer, cassette: &Cassette) {
+    let sent = server
+        .requests()
+        .into_iter()
+        .map(|request| (request.method, request.path))
+        .collect::<Vec<_>>();
+    let recorded = cassette
+        .interactions
+        .iter()
+        .map(|interaction| {
+            (
+                interaction.request.method.clone(),
+                interaction.request.path.clone(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(sent, recorded);
+}
+
+#[tokio::test]
+async fn replays_fetch_extract_and_summarize() {
+    let (server, cassette) = replay("rust-tips").await;
+    let client = MediumClient::new("sid=abc").unwrap();
+    let agent = Claude3agent::from_url(server.url("/v1/messages"), "key").unwrap();
+
+    let summary = summarize_with_client(
+        &client,
+        &server.url("/@janedoe/five-rust-tips-0123456789ab?source=rss"),
+        &agent,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        summary,
+        "- Split big crates into a workspace so cargo rebuilds only what changed.\n\
+         - Lower debug info in the dev profile and use a faster linker like mold."
+    );
+    assert_replayed(&server, &cassette);
+    let sent: serde_json::Value = serde_json::from_str(&server.requests()[1].body).unwrap();
+    let article = sent["messages"][0]["content"].as_str().unwrap();
+    assert!(article.contains("Splitting a big crate into a workspace"));
+    assert!(article.contains("faster linker such as mold"));
+    assert!(article.contains("debug = 1"));
+}
+
+#[tokio::test]
+async fn replays_into_a_markdown_note() {
+    let (server, cassette) = replay("rust-tips").await;
+    let client = MediumClient::new("sid=abc").unwrap();
+    let agent = Claude3agent::from_url(server.url("/v1/messages"), "key").unwrap();
+    let dir = std::env::temp_dir().join(format!("summary-replay-{}", std::process::id()));
+
+    let path = summarize_url_to_markdown_file_with_client(
+        &client,
+        &server.url("/@janedoe/five-rust-tips-0123456789ab"),
+        &agent,
+        &dir,
+    )
+    .await
+    .unwrap();
+
+    assert_replayed(&server, &cassette);
+    assert_eq!(
+        path,
+        dir.join("five-rust-tips-for-faster-builds-0123456789ab.md")
+    );
+    let markdown = std::fs::read_to_string(&path).unwrap();
+    assert!(markdown.starts_with("---\ntitle: \"Five Rust Tips for Faster Builds\"\n"));
+    assert!(markdown.contains("author: \"Jane Doe\""));
+    assert!(markdown.contains("- Lower debug info in the dev profile"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
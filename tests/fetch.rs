@@ -0,0 +1,1163 @@
+mod common;
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use common::{MockResponse, MockServer};
+use summary_medium_post::{
+    chunk_fields, content_checksum, extract_article_links, extract_digest_links, normalize_url,
+    post_id, verify_checksum, Article, ArticleResponse, CancelFlag, ClientError, ContentBlock,
+    ContentCheck, ExtractOptions, ExtractorRegistry, FetchOutcome, ListItem, MediumClient,
+    PublishedSince, RetryPolicy, DEFAULT_TEXT_PATTERN, DEFAULT_USER_AGENT,
+};
+
+const PAGE: &str = r#"<html><script>{"type":"P","text":"Hello from the mock"}</script></html>"#;
+
+#[tokio::test]
+async fn fetch_returns_body_and_status() {
+    let server = MockServer::start(vec![MockResponse::new(200, PAGE)]).await;
+    let client = MediumClient::new("sid=abc").unwrap();
+
+    let data = client.fetch(&server.url("/@me/post")).await.unwrap();
+
+    assert_eq!(data.status(), "200");
+    assert_eq!(data.body(), PAGE);
+    assert_eq!(data.url(), server.url("/@me/post"));
+    assert_eq!(data.content().unwrap(), "Hello from the mock");
+    assert_eq!(
+        data.to_string(),
+        format!("{} (200, {} bytes)", server.url("/@me/post"), PAGE.len())
+    );
+}
+
+#[tokio::test]
+async fn fetch_not_found_is_bad_status() {
+    let server = MockServer::start(vec![MockResponse::new(404, "gone")]).await;
+    let client = MediumClient::new("sid=abc").unwrap();
+
+    let err = client.fetch(&server.url("/missing")).await.unwrap_err();
+
+    match err {
+        ClientError::BadStatus { status, url } => {
+            assert_eq!(status, 404);
+            assert_eq!(url, server.url("/missing"));
+        }
+        other => panic!("expected BadStatus, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn fetch_sends_cookie_and_user_agent() {
+    let server = MockServer::start(vec![MockResponse::new(200, PAGE)]).await;
+    let client = MediumClient::new("sid=abc; uid=42").unwrap();
+
+    client.fetch(&server.url("/@me/post")).await.unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    let request = &requests[0];
+    assert_eq!(request.method, "GET");
+    assert_eq!(request.path, "/@me/post");
+    assert_eq!(request.header("cookie"), Some("sid=abc; uid=42"));
+    assert!(request
+        .header("user-agent")
+        .is_some_and(|ua| ua.contains("Firefox")));
+}
+
+#[tokio::test]
+async fn every_medium_header_has_its_value() {
+    let server = MockServer::start(vec![MockResponse::new(200, PAGE)]).await;
+    let client = MediumClient::new("sid=abc; uid=42").unwrap();
+
+    client.fetch(&server.url("/@me/post")).await.unwrap();
+    // the json api request and the page fallback go through the same headers
+    let _ = client
+        .fetch_story(&server.url("/@me/post-0123456789ab"))
+        .await;
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    for request in requests {
+        assert_eq!(request.header("cookie"), Some("sid=abc; uid=42"));
+        assert_eq!(request.header("origin"), Some("https://medium.com"));
+        assert_eq!(request.header("user-agent"), Some(DEFAULT_USER_AGENT));
+        assert_eq!(request.header("accept-encoding"), Some("gzip"));
+    }
+}
+
+#[tokio::test]
+async fn rotates_through_user_agent_pool() {
+    let server = MockServer::start(vec![MockResponse::new(200, PAGE)]).await;
+    let client = MediumClient::builder("sid=abc")
+        .user_agents(["UA-1", "UA-2"])
+        .build()
+        .unwrap();
+
+    for _ in 0..3 {
+        client
+            .clone()
+            .fetch(&server.url("/@me/post"))
+            .await
+            .unwrap();
+    }
+
+    let agents = server
+        .requests()
+        .iter()
+        .map(|r| r.header("user-agent").unwrap().to_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(agents, ["UA-1", "UA-2", "UA-1"]);
+}
+
+#[tokio::test]
+async fn cancelled_client_sends_nothing() {
+    let server = MockServer::start(vec![MockResponse::new(200, PAGE)]).await;
+    let cancel = CancelFlag::new();
+    let client = MediumClient::new("sid=abc")
+        .unwrap()
+        .with_cancel_flag(cancel.clone());
+
+    client.fetch(&server.url("/@me/post")).await.unwrap();
+    cancel.cancel();
+    let err = client.fetch(&server.url("/@me/post")).await.unwrap_err();
+
+    assert!(matches!(err, ClientError::Cancelled));
+    assert_eq!(server.requests().len(), 1);
+}
+
+fn quick_retry(max_retries: usize) -> RetryPolicy {
+    RetryPolicy::new(max_retries).initial_backoff(Duration::from_millis(1))
+}
+
+#[tokio::test]
+async fn retry_recovers_from_server_error_and_empty_body() {
+    let server = MockServer::start(vec![
+        MockResponse::new(503, "busy"),
+        MockResponse::new(200, "  "),
+        MockResponse::new(200, PAGE),
+    ])
+    .await;
+    let client = MediumClient::new("sid=abc")
+        .unwrap()
+        .with_retry(quick_retry(3));
+
+    let data = client.fetch(&server.url("/@me/post")).await.unwrap();
+
+    assert_eq!(data.body(), PAGE);
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn retry_gives_up_after_max_retries() {
+    let server = MockServer::start(vec![MockResponse::new(503, "busy")]).await;
+    let client = MediumClient::new("sid=abc")
+        .unwrap()
+        .with_retry(quick_retry(2));
+
+    let err = client.fetch(&server.url("/@me/post")).await.unwrap_err();
+
+    assert!(matches!(err, ClientError::BadStatus { status: 503, .. }));
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn not_found_is_not_retried() {
+    let server = MockServer::start(vec![MockResponse::new(404, "gone")]).await;
+    let client = MediumClient::new("sid=abc")
+        .unwrap()
+        .with_retry(quick_retry(2));
+
+    client.fetch(&server.url("/missing")).await.unwrap_err();
+
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn page_without_text_is_miss_match() {
+    let server = MockServer::start(vec![MockResponse::new(200, "<html>Sign in</html>")]).await;
+    let client = MediumClient::new("sid=abc").unwrap();
+
+    let data = client.fetch(&server.url("/@me/post")).await.unwrap();
+
+    assert!(matches!(data.content(), Err(ClientError::MissMatch)));
+}
+
+#[tokio::test]
+async fn requests_go_through_proxy() {
+    let proxy = MockServer::start(vec![MockResponse::new(200, PAGE)]).await;
+    let client = MediumClient::builder("sid=abc")
+        .proxy(proxy.url(""))
+        .build()
+        .unwrap();
+
+    client
+        .fetch("http://medium.invalid/@me/post")
+        .await
+        .unwrap();
+
+    assert_eq!(proxy.requests()[0].path, "http://medium.invalid/@me/post");
+}
+
+#[tokio::test]
+async fn body_over_limit_is_rejected() {
+    let server = MockServer::start(vec![MockResponse::new(200, "x".repeat(2048))]).await;
+    let client = MediumClient::builder("sid=abc")
+        .max_body_bytes(1024)
+        .build()
+        .unwrap();
+
+    let err = client.fetch(&server.url("/@me/post")).await.unwrap_err();
+
+    assert!(matches!(err, ClientError::BodyTooLarge { limit: 1024, .. }));
+}
+
+#[tokio::test]
+async fn shared_client_still_sends_medium_headers() {
+    let server = MockServer::start(vec![MockResponse::new(200, PAGE)]).await;
+    let shared = reqwest::Client::new();
+    let client = MediumClient::with_client(shared.clone(), "sid=abc").unwrap();
+
+    client.fetch(&server.url("/@me/post")).await.unwrap();
+
+    let request = &server.requests()[0];
+    assert_eq!(request.header("cookie"), Some("sid=abc"));
+    assert_eq!(request.header("origin"), Some("https://medium.com"));
+}
+
+#[tokio::test]
+async fn meta_reads_subtitle_from_description() {
+    let page = r#"<html><head><meta name="description" content="Why &quot;less&quot; is more"></head>
+<script>{"subtitle":"ignored"}</script></html>"#;
+    let server = MockServer::start(vec![MockResponse::new(200, page)]).await;
+    let client = MediumClient::new("sid=abc").unwrap();
+
+    let data = client.fetch(&server.url("/@me/post")).await.unwrap();
+
+    assert_eq!(
+        data.meta().unwrap().subtitle.as_deref(),
+        Some("Why \"less\" is more")
+    );
+}
+
+#[tokio::test]
+async fn gzip_body_is_decoded_before_extraction() {
+    let page = std::fs::read(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/post.html.gz"
+    ))
+    .unwrap();
+    let server = MockServer::start(vec![
+        MockResponse::bytes(200, page.clone()).header("content-encoding", "gzip"),
+        // sent compressed without saying so
+        MockResponse::bytes(200, page),
+    ])
+    .await;
+    let client = MediumClient::new("sid=abc").unwrap();
+
+    for _ in 0..2 {
+        let data = client.fetch(&server.url("/@me/post")).await.unwrap();
+        let content = data.content().unwrap();
+        assert!(content.starts_with("Rust's ownership model makes data races"));
+        assert!(content.ends_with("you never write one."));
+    }
+}
+
+#[tokio::test]
+async fn undecodable_bodies_are_errors() {
+    let server = MockServer::start(vec![
+        MockResponse::new(200, "compressed").header("content-encoding", "br"),
+        MockResponse::new(200, "\u{1f}\u{8b}garbage").header("content-encoding", "gzip"),
+    ])
+    .await;
+    let client = MediumClient::new("sid=abc").unwrap();
+
+    let err = client.fetch(&server.url("/@me/post")).await.unwrap_err();
+    assert!(matches!(
+        err,
+        ClientError::UnsupportedEncoding { ref encoding, .. } if encoding == "br"
+    ));
+    let err = client.fetch(&server.url("/@me/post")).await.unwrap_err();
+    assert!(matches!(
+        err,
+        ClientError::DecodeFailed {
+            encoding: "gzip",
+            ..
+        }
+    ));
+}
+
+#[tokio::test]
+async fn custom_text_pattern_replaces_default() {
+    let page = r#"<html><script>{"body":"New layout","text":"old"}</script></html>"#;
+    let server = MockServer::start(vec![MockResponse::new(200, page)]).await;
+    let client = MediumClient::new("sid=abc").unwrap();
+    let data = client.fetch(&server.url("/@me/post")).await.unwrap();
+
+    let options = ExtractOptions::default()
+        .with_text_pattern(r#""body":"([^"]*)""#)
+        .unwrap();
+
+    assert_eq!(data.content_with(&options).unwrap(), "New layout");
+    assert_eq!(options.text_pattern(), r#""body":"([^"]*)""#);
+}
+
+#[test]
+fn text_pattern_needs_one_capture_group() {
+    for pattern in [r"text", r"(a)(b)", r"(unclosed"] {
+        assert!(matches!(
+            ExtractOptions::default().with_text_pattern(pattern),
+            Err(ClientError::RegexError(_))
+        ));
+    }
+    assert_eq!(
+        ExtractOptions::default().text_pattern(),
+        DEFAULT_TEXT_PATTERN
+    );
+}
+
+#[test]
+fn post_id_from_story_urls() {
+    assert_eq!(
+        post_id("https://medium.com/@me/rust-tips-0123456789ab?source=feed").unwrap(),
+        "0123456789ab"
+    );
+    assert_eq!(
+        post_id("https://blog.example.com/p/7fe9e55da4e1").unwrap(),
+        "7fe9e55da4e1"
+    );
+    assert!(matches!(
+        post_id("https://medium.com/@me"),
+        Err(ClientError::UnsupportedUrl(_))
+    ));
+}
+
+#[tokio::test]
+async fn next_part_needs_a_part_number_in_the_title() {
+    let links = r#"<a href="/@me/rust-async-part-2-7fe9e55da4e1">Part 2</a>
+<a href="/@me/other-series-part-5-abcdef012345">Read the next part</a>"#;
+    let server = MockServer::start(vec![
+        MockResponse::new(200, format!("<title>Rust async, Part 1</title>{links}")),
+        MockResponse::new(200, format!("<title>Why I like Rust</title>{links}")),
+    ])
+    .await;
+    let client = MediumClient::new("sid=abc").unwrap();
+
+    let first = client
+        .fetch(&server.url("/@me/rust-async-0123456789ab"))
+        .await
+        .unwrap();
+    let unrelated = client
+        .fetch(&server.url("/@me/like-rust-0123456789ab"))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        first.next_part_url().unwrap(),
+        Some(server.url("/@me/rust-async-part-2-7fe9e55da4e1"))
+    );
+    assert_eq!(unrelated.next_part_url().unwrap(), None);
+}
+
+#[tokio::test]
+async fn edited_page_is_content_changed() {
+    let server = MockServer::start(vec![
+        MockResponse::new(200, r#"<script>{"type":"P","text":"First draft"}</script>"#),
+        MockResponse::new(200, r#"<script>{"type":"P","text":"First draft"}</script>"#),
+        MockResponse::new(
+            200,
+            r#"<script>{"type":"P","text":"Edited draft"}</script>"#,
+        ),
+    ])
+    .await;
+    let client = MediumClient::new("sid=abc").unwrap();
+    let url = server.url("/@me/post");
+    let stored = content_checksum(&client.fetch(&url).await.unwrap().content().unwrap());
+
+    let same = client.fetch(&url).await.unwrap().content().unwrap();
+    let edited = client.fetch(&url).await.unwrap().content().unwrap();
+
+    assert_eq!(verify_checksum(&stored, &same), ContentCheck::Unchanged);
+    assert_eq!(
+        verify_checksum(&stored, &edited),
+        ContentCheck::ContentChanged {
+            previous: stored.clone(),
+            current: content_checksum(&edited),
+        }
+    );
+}
+
+#[tokio::test]
+async fn redirect_to_signin_needs_authentication() {
+    let server = MockServer::start(vec![
+        MockResponse::new(302, "").header(
+            "location",
+            "/m/signin?redirect=%2F%40me%2Fpost-0123456789ab",
+        ),
+        MockResponse::new(200, "<html>Sign in to Medium</html>"),
+    ])
+    .await;
+    let client = MediumClient::new("sid=expired").unwrap();
+
+    let err = client
+        .fetch(&server.url("/@me/post-0123456789ab"))
+        .await
+        .unwrap_err();
+
+    assert!(matches!(err, ClientError::AuthenticationRequired(url) if url.contains("/m/signin")));
+    assert_eq!(
+        server.requests()[1].path,
+        "/m/signin?redirect=%2F%40me%2Fpost-0123456789ab"
+    );
+}
+
+#[tokio::test]
+async fn check_passes_for_a_signed_in_cookie() {
+    let server = MockServer::start(vec![MockResponse::new(200, "<html>Settings</html>")]).await;
+    let client = MediumClient::builder("sid=abc")
+        .check_url(server.url("/me/settings"))
+        .build()
+        .unwrap();
+
+    client.check().await.unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests[0].path, "/me/settings");
+    assert_eq!(requests[0].header("cookie"), Some("sid=abc"));
+}
+
+#[tokio::test]
+async fn check_fails_for_a_signed_out_cookie() {
+    let server = MockServer::start(vec![
+        MockResponse::new(302, "").header("location", "/m/signin?redirect=%2Fme%2Fsettings"),
+        MockResponse::new(200, "<html>Sign in to Medium</html>"),
+    ])
+    .await;
+    let client = MediumClient::builder("sid=expired")
+        .check_url(server.url("/me/settings"))
+        .build()
+        .unwrap();
+
+    let err = client.check().await.unwrap_err();
+
+    assert!(matches!(err, ClientError::AuthenticationRequired(url) if url.contains("/m/signin")));
+}
+
+#[tokio::test]
+async fn unchanged_page_is_not_modified() {
+    let server = MockServer::start(vec![
+        MockResponse::new(200, "<p>story</p>")
+            .header("etag", "\"v1\"")
+            .header("last-modified", "Wed, 21 Oct 2015 07:28:00 GMT"),
+        MockResponse::new(304, ""),
+    ])
+    .await;
+    let client = MediumClient::new("sid=abc").unwrap();
+    let url = server.url("/@me/post");
+    let previous = client.fetch(&url).await.unwrap();
+
+    let outcome = client.fetch_if_modified(&url, &previous).await.unwrap();
+
+    assert!(matches!(outcome, FetchOutcome::NotModified));
+    let request = &server.requests()[1];
+    assert_eq!(request.header("if-none-match"), Some("\"v1\""));
+    assert_eq!(
+        request.header("if-modified-since"),
+        Some("Wed, 21 Oct 2015 07:28:00 GMT")
+    );
+}
+
+#[tokio::test]
+async fn changed_page_is_fetched_like_a_plain_fetch() {
+    let server = MockServer::start(vec![
+        MockResponse::new(200, "<article>v1</article>").header("etag", "\"v1\""),
+        MockResponse::new(503, "busy"),
+        MockResponse::new(200, "<article>v2</article>").header("etag", "\"v2\""),
+    ])
+    .await;
+    let registry = ExtractorRegistry::new().register("127.0.0.1", |body: &str| {
+        let start = body.find("<article>").ok_or(ClientError::MissMatch)? + "<article>".len();
+        let end = body.find("</article>").ok_or(ClientError::MissMatch)?;
+        Ok(body[start..end].to_owned())
+    });
+    let client = MediumClient::builder("sid=abc")
+        .extractors(registry)
+        .build()
+        .unwrap()
+        .with_retry(quick_retry(2));
+    let url = server.url("/2024/notes");
+    let previous = client.fetch(&url).await.unwrap();
+
+    let outcome = client.fetch_if_modified(&url, &previous).await.unwrap();
+
+    let FetchOutcome::Modified(data) = outcome else {
+        panic!("expected the changed page");
+    };
+    assert_eq!(data.content().unwrap(), "v2");
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    // the retry is as conditional as the first attempt
+    assert!(requests[1..]
+        .iter()
+        .all(|request| request.header("if-none-match") == Some("\"v1\"")));
+}
+
+#[test]
+fn normalize_url_keeps_friend_link_token() {
+    assert_eq!(
+        normalize_url("https://medium.com/p/0123456789ab?sk=f00d&source=friends_link").unwrap(),
+        "https://medium.com/p/0123456789ab?sk=f00d"
+    );
+    assert_eq!(
+        normalize_url(
+            "https://medium.com/@me/tips-0123456789ab?source=rss----5517fd7b58a6---4&utm_medium=email"
+        )
+        .unwrap(),
+        "https://medium.com/@me/tips-0123456789ab"
+    );
+    assert_eq!(
+        normalize_url("https://medium.com/@me/tips-0123456789ab").unwrap(),
+        "https://medium.com/@me/tips-0123456789ab"
+    );
+    assert!(matches!(
+        normalize_url("not a url"),
+        Err(ClientError::InvalidUrl(_))
+    ));
+}
+
+#[tokio::test]
+async fn fetch_drops_source_but_sends_friend_token() {
+    let server = MockServer::start(vec![MockResponse::new(200, "<p>story</p>")]).await;
+    let client = MediumClient::new("sid=abc").unwrap();
+
+    client
+        .fetch(&server.url("/p/0123456789ab?source=friends_link&sk=f00d"))
+        .await
+        .unwrap();
+
+    assert_eq!(server.requests()[0].path, "/p/0123456789ab?sk=f00d");
+}
+
+#[tokio::test]
+async fn fetch_story_reads_json_api() {
+    let json = r#"])}while(1);</x>{"payload":{"value":{"title":"Tips","creatorId":"u1","content":{"bodyModel":{"paragraphs":[{"type":3,"text":"Tips"},{"type":1,"text":"First point"}]}}},"references":{"User":{"u1":{"name":"Me"}}}}}"#;
+    let server = MockServer::start(vec![MockResponse::new(200, json)]).await;
+    let client = MediumClient::new("sid=abc").unwrap();
+
+    let article = client
+        .fetch_story(&server.url("/@me/tips-0123456789ab"))
+        .await
+        .unwrap();
+
+    assert_eq!(article.title.as_deref(), Some("Tips"));
+    assert_eq!(article.author.as_deref(), Some("Me"));
+    assert_eq!(article.paragraphs, ["Tips", "First point"]);
+    assert_eq!(article.language, None);
+    assert_eq!(server.requests()[0].path, "/_/api/posts/0123456789ab");
+}
+
+#[tokio::test]
+async fn fetch_story_falls_back_to_the_page() {
+    let page =
+        r#"<html><script>{"__typename":"Post","type":"P","text":"From the page"}</script></html>"#;
+    let server = MockServer::start(vec![
+        MockResponse::new(404, "not found"),
+        MockResponse::new(200, page),
+    ])
+    .await;
+    let client = MediumClient::new("sid=abc").unwrap();
+
+    let article = client
+        .fetch_story(&server.url("/@me/tips-0123456789ab"))
+        .await
+        .unwrap();
+
+    assert_eq!(article.paragraphs, ["From the page"]);
+    assert_eq!(server.requests()[1].path, "/@me/tips-0123456789ab");
+}
+
+#[tokio::test]
+async fn locked_story_served_signed_out_is_auth_expired() {
+    let page = r#"<html><script>{"viewer":null,"Post:1":{"__typename":"Post","isLocked":true},"type":"P","text":"Preview"}</script></html>"#;
+    let server = MockServer::start(vec![MockResponse::new(200, page)]).await;
+    let url = server.url("/@me/tips-0123456789ab");
+
+    let err = MediumClient::new("sid=expired")
+        .unwrap()
+        .fetch_article(&url)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ClientError::AuthExpired(_)));
+
+    // without a cookie the preview is all anyone could expect
+    let data = MediumClient::new("")
+        .unwrap()
+        .fetch_article(&url)
+        .await
+        .unwrap();
+    assert!(data.is_locked().unwrap());
+}
+
+#[tokio::test]
+async fn other_locked_posts_dont_lock_the_story() {
+    let page = r#"<html><script>{"viewer":null,"Post:0123456789ab":{"__typename":"Post","isLocked":false,"creator":{"__ref":"User:1"}},"Post:7fe9e55da4e1":{"__typename":"Post","isLocked":true},"type":"P","text":"Free story"}</script></html>"#;
+    let server = MockServer::start(vec![MockResponse::new(200, page)]).await;
+    let client = MediumClient::new("sid=abc").unwrap();
+
+    let data = client
+        .fetch_article(&server.url("/@me/free-0123456789ab"))
+        .await
+        .unwrap();
+    assert!(!data.is_locked().unwrap());
+
+    // the recommended story is the locked one
+    let err = client
+        .fetch_article(&server.url("/@me/member-7fe9e55da4e1"))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ClientError::AuthExpired(_)));
+}
+
+#[tokio::test]
+async fn deleted_and_unlisted_posts_have_their_own_errors() {
+    let deleted =
+        r#"<html><script>{"Post:1":{"__typename":"Post","isDeleted":true}}</script></html>"#;
+    let unlisted = r#"<html><script>{"Post:1":{"__typename":"Post","visibility":"UNLISTED"},"type":"P","text":""}</script></html>"#;
+    let readable = r#"<html><script>{"Post:1":{"__typename":"Post","visibility":"UNLISTED"},"type":"P","text":"Shared by link"}</script></html>"#;
+    let server = MockServer::start(vec![
+        MockResponse::new(200, deleted),
+        MockResponse::new(410, "gone"),
+        MockResponse::new(200, unlisted),
+        MockResponse::new(200, readable),
+    ])
+    .await;
+    let client = MediumClient::new("sid=abc").unwrap();
+    let url = server.url("/@me/tips-0123456789ab");
+
+    for _ in 0..2 {
+        let err = client.fetch_article(&url).await.unwrap_err();
+        assert!(matches!(err, ClientError::PostDeleted(ref u) if *u == url));
+    }
+    let err = client.fetch_article(&url).await.unwrap_err();
+    assert!(matches!(err, ClientError::PostUnlisted(_)));
+    let data = client.fetch_article(&url).await.unwrap();
+    assert!(data.is_unlisted().unwrap());
+    assert_eq!(data.content().unwrap(), "Shared by link");
+}
+
+#[tokio::test]
+async fn deleted_related_posts_and_notice_text_dont_delete_the_story() {
+    let page = r#"<html><script>{"Post:0123456789ab":{"__typename":"Post","isDeleted":false},"Post:7fe9e55da4e1":{"__typename":"Post","isDeleted":true},"type":"P","text":"This story was deleted and rewritten from scratch"}</script></html>"#;
+    let server = MockServer::start(vec![MockResponse::new(200, page)]).await;
+    let client = MediumClient::new("sid=abc").unwrap();
+
+    let data = client
+        .fetch_article(&server.url("/@me/rewrite-0123456789ab"))
+        .await
+        .unwrap();
+
+    assert!(!data.is_deleted().unwrap());
+    assert!(data
+        .content()
+        .unwrap()
+        .starts_with("This story was deleted"));
+}
+
+#[tokio::test]
+async fn subtitle_comes_from_the_story_entry() {
+    let page = r#"<html><script>{"Post:7fe9e55da4e1":{"__typename":"Post","previewContent":{"subtitle":"A related story"}},"Post:0123456789ab":{"__typename":"Post","previewContent":{"subtitle":"This one"}}}</script></html>"#;
+    let server = MockServer::start(vec![MockResponse::new(200, page)]).await;
+    let client = MediumClient::new("sid=abc").unwrap();
+
+    let data = client
+        .fetch(&server.url("/@me/story-0123456789ab"))
+        .await
+        .unwrap();
+
+    assert_eq!(data.meta().unwrap().subtitle.as_deref(), Some("This one"));
+}
+
+#[tokio::test]
+async fn cover_image_comes_from_the_story_entry() {
+    let page = std::fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/cover.html"
+    ))
+    .unwrap();
+    let server = MockServer::start(vec![MockResponse::new(200, page)]).await;
+    let client = MediumClient::new("sid=abc").unwrap();
+
+    let data = client
+        .fetch(&server.url("/@janedoe/borrowing-without-tears-0123456789ab"))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        data.meta().unwrap().cover_image.as_deref(),
+        Some("https://miro.medium.com/v2/resize:fit:1200/1*borrowing-cover.jpeg")
+    );
+}
+
+#[tokio::test]
+async fn publish_date_comes_from_the_story_entry() {
+    let page = r#"<html><script>{"Post:7fe9e55da4e1":{"__typename":"Post","firstPublishedAt":1600000000000},"Post:0123456789ab":{"__typename":"Post","firstPublishedAt":1720000000000}}</script></html>"#;
+    let server = MockServer::start(vec![MockResponse::new(200, page)]).await;
+    let client = MediumClient::new("sid=abc").unwrap();
+
+    let data = client
+        .fetch(&server.url("/@me/story-0123456789ab"))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        data.meta()
+            .unwrap()
+            .published_at
+            .map(|at| at.unix_timestamp()),
+        Some(1_720_000_000)
+    );
+}
+
+#[tokio::test]
+async fn badges_come_from_the_story_entry() {
+    let page = r#"<html><script>{"Post:0123456789ab":{"__typename":"Post","isFeatured":true,"isBoosted":false,"pinnedAt":0},"Post:7fe9e55da4e1":{"__typename":"Post","isFeatured":false,"isBoosted":true,"pinnedAt":1710000000000}}</script></html>"#;
+    let server = MockServer::start(vec![MockResponse::new(200, page)]).await;
+    let client = MediumClient::new("sid=abc").unwrap();
+
+    let meta = client
+        .fetch(&server.url("/@me/featured-0123456789ab"))
+        .await
+        .unwrap()
+        .meta()
+        .unwrap();
+    assert!(meta.featured);
+    assert!(!meta.boosted);
+    assert!(!meta.pinned);
+
+    let meta = client
+        .fetch(&server.url("/@me/boosted-7fe9e55da4e1"))
+        .await
+        .unwrap()
+        .meta()
+        .unwrap();
+    assert!(!meta.featured);
+    assert!(meta.boosted);
+    assert!(meta.pinned);
+}
+
+#[tokio::test]
+async fn media_lists_images_and_external_links() {
+    let page = r#"<html><script>{"previewImage":{"__ref":"ImageMetadata:cover.png"},
+"Paragraph:1":{"type":"IMG","metadata":{"__ref":"ImageMetadata:1*chart.png"},"text":""},
+"Paragraph:2":{"type":"P","markups":[{"__typename":"Markup","type":"A","href":"https:\/\/docs.rs\/regex","anchorType":"LINK"},{"type":"A","href":"https://medium.com/@other","anchorType":"LINK"},{"type":"STRONG"}],"text":"See docs"}}</script></html>"#;
+    let server = MockServer::start(vec![MockResponse::new(200, page)]).await;
+    let client = MediumClient::new("sid=abc").unwrap();
+    let data = client.fetch(&server.url("/@me/post")).await.unwrap();
+
+    let media = data.media().unwrap();
+
+    assert_eq!(
+        media.images,
+        ["https://miro.medium.com/v2/resize:fit:1200/1*chart.png"]
+    );
+    assert_eq!(media.links, ["https://docs.rs/regex"]);
+}
+
+#[tokio::test]
+async fn article_records_detected_language() {
+    let page = r#"<html><script>{"type":"P","text":"บทความภาษาไทย"}</script></html>"#;
+    let server = MockServer::start(vec![MockResponse::new(200, page)]).await;
+    let client = MediumClient::new("sid=abc").unwrap();
+
+    let data = client.fetch(&server.url("/@me/post")).await.unwrap();
+
+    assert_eq!(data.article().unwrap().language.as_deref(), Some("thai"));
+}
+
+#[tokio::test]
+async fn repeated_paragraphs_are_extracted_once() {
+    let page = r#"<html><script>{"name":"a1","type":"P","text":"Intro"},{"name":"a2","type":"P","text":"Body"}</script>
+<script>{"Paragraph:1":{"name":"a1","type":"P","text":"Intro"},"Paragraph:2":{"name":"a2","type":"P","text":"Body"}}</script>
+<script>{"name":"a3","type":"P","text":"Outro"}</script></html>"#;
+    let server = MockServer::start(vec![MockResponse::new(200, page)]).await;
+    let client = MediumClient::new("sid=abc").unwrap();
+
+    let data = client.fetch(&server.url("/@me/post")).await.unwrap();
+
+    assert_eq!(data.content().unwrap(), "Intro Body Outro");
+}
+
+#[tokio::test]
+async fn repeated_text_of_different_paragraphs_is_kept() {
+    let page = r#"<html><script>{"Paragraph:p_0":{"type":"P","text":"Step one"},"Paragraph:p_1":{"type":"P","text":"Run the tests."},
+"Paragraph:p_2":{"type":"P","text":"Step two"},"Paragraph:p_3":{"type":"P","text":"Run the tests."}}</script>
+<script>{"type":"P","text":"Share"},{"type":"P","text":"Share"}</script></html>"#;
+    let server = MockServer::start(vec![MockResponse::new(200, page)]).await;
+    let client = MediumClient::new("sid=abc").unwrap();
+
+    let data = client.fetch(&server.url("/@me/post")).await.unwrap();
+
+    assert_eq!(
+        data.content().unwrap(),
+        "Step one Run the tests. Step two Run the tests. Share"
+    );
+}
+
+#[tokio::test]
+async fn text_capture_ends_at_the_right_quote() {
+    let pages = [
+        // escaped backslash right before the closing quote
+        (
+            r#"{"type":"P","text":"C:\\"},{"type":"P","text":"next"}"#,
+            r#"C:\\ next"#,
+        ),
+        // escaped backslash followed by an escaped quote
+        (
+            r#"{"type":"P","text":"a \\\" b"},{"type":"P","text":"c"}"#,
+            r#"a \\\" b c"#,
+        ),
+        // keys ending in text are other fields
+        (
+            r#"{"alttext":"chart","subtext":"x","type":"P","text":"Body"}"#,
+            "Body",
+        ),
+        // a field cut off at the end of the body is dropped, not merged
+        (
+            r#"{"type":"P","text":"whole"},{"type":"P","text":"cut \"#,
+            "whole",
+        ),
+    ];
+    for (page, expected) in pages {
+        let page = format!("<html><script>{page}</script></html>");
+        let server = MockServer::start(vec![MockResponse::new(200, page)]).await;
+        let client = MediumClient::new("sid=abc").unwrap();
+
+        let data = client.fetch(&server.url("/@me/post")).await.unwrap();
+
+        assert_eq!(data.content().unwrap(), expected);
+        let options = ExtractOptions {
+            take_paragraphs: Some(9),
+            ..ExtractOptions::default()
+        };
+        assert_eq!(data.content_with(&options).unwrap(), expected);
+    }
+}
+
+#[tokio::test]
+async fn text_fields_match_content_and_chunk_lazily() {
+    let page = r#"<html><script>{"name":"a1","type":"P","text":"Intro words"},{"name":"a2","type":"PRE","text":"let x = 1;"},{"name":"a3","type":"P","text":"Body"}</script>
+<script>{"name":"a1","type":"P","text":"Intro words"}</script></html>"#;
+    let server = MockServer::start(vec![MockResponse::new(200, page)]).await;
+    let client = MediumClient::new("sid=abc").unwrap();
+    let options = ExtractOptions {
+        skip_code: true,
+        ..ExtractOptions::default()
+    };
+
+    let data = client.fetch(&server.url("/@me/post")).await.unwrap();
+    let fields = data.text_fields(&options).unwrap().collect::<Vec<_>>();
+
+    assert_eq!(fields, ["Intro words", "Body"]);
+    assert_eq!(fields.join(" "), data.content_with(&options).unwrap());
+    assert_eq!(
+        chunk_fields(data.text_fields(&options).unwrap(), 3),
+        ["Intro words", "Body"]
+    );
+}
+
+#[tokio::test]
+async fn list_page_links_every_story_once() {
+    let page = r#"<html><a href="/@me/first-story-0123456789ab?source=tag_page">First</a>
+<a href="https://medium.com/tag/rust">Tag</a>
+<a href="/@me/first-story-0123456789ab">First again</a>
+<script>{"mediumUrl":"https:\/\/blog.example.com\/second-7fe9e55da4e1","creator":{"mediumUrl":"https:\/\/medium.com\/@me"}}</script></html>"#;
+    let server = MockServer::start(vec![MockResponse::new(200, page)]).await;
+    let client = MediumClient::new("sid=abc").unwrap();
+    let data = client.fetch(&server.url("/tag/rust")).await.unwrap();
+
+    let links = extract_article_links(&data).unwrap();
+
+    assert_eq!(
+        links,
+        [
+            server.url("/@me/first-story-0123456789ab"),
+            "https://blog.example.com/second-7fe9e55da4e1".to_owned(),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn links_published_since_reads_listing_dates_first() {
+    let list = r#"<html><script>{"Post:aaaaaaaaaaaa":{"__typename":"Post","creator":{"__ref":"User:1"},"firstPublishedAt":1700000000000,"mediumUrl":"https:\/\/medium.com\/@me\/old-aaaaaaaaaaaa"},"Post:bbbbbbbbbbbb":{"__typename":"Post","firstPublishedAt":1720000000000,"mediumUrl":"https://medium.com/@me/new-bbbbbbbbbbbb"}}</script>
+<a href="/@me/undated-cccccccccccc">Undated</a></html>"#;
+    let story = r#"<html><script>{"__typename":"Post","firstPublishedAt":1725000000000,"type":"P","text":"Hi"}</script></html>"#;
+    let server = MockServer::start(vec![
+        MockResponse::new(200, list),
+        MockResponse::new(200, story),
+    ])
+    .await;
+    let client = MediumClient::new("sid=abc").unwrap();
+    let list = client.fetch(&server.url("/tag/rust")).await.unwrap();
+    // 2024-01-01
+    let since = time::OffsetDateTime::from_unix_timestamp(1_704_067_200).unwrap();
+
+    let links = client
+        .links_published_since(&list, &PublishedSince::new(since))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        links,
+        [
+            "https://medium.com/@me/new-bbbbbbbbbbbb".to_owned(),
+            server.url("/@me/undated-cccccccccccc"),
+        ]
+    );
+    // only the story the listing has no date for was fetched
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1].path, "/@me/undated-cccccccccccc");
+    assert!(!PublishedSince::new(since)
+        .include_unknown(false)
+        .allows(None));
+}
+
+#[tokio::test]
+async fn stories_published_since_keep_the_pages_they_fetched() {
+    let list = r#"<html><script>{"Post:aaaaaaaaaaaa":{"__typename":"Post","firstPublishedAt":1720000000000,"mediumUrl":"https://medium.com/@me/new-aaaaaaaaaaaa"}}</script>
+<a href="/@me/undated-cccccccccccc">Undated</a></html>"#;
+    let story = r#"<html><script>{"__typename":"Post","firstPublishedAt":1725000000000,"type":"P","text":"Hi"}</script></html>"#;
+    let server = MockServer::start(vec![
+        MockResponse::new(200, list),
+        MockResponse::new(200, story),
+    ])
+    .await;
+    let client = MediumClient::new("sid=abc").unwrap();
+    let list = client.fetch(&server.url("/tag/rust")).await.unwrap();
+    let since = time::OffsetDateTime::from_unix_timestamp(1_704_067_200).unwrap();
+
+    let stories = client
+        .stories_published_since(&list, &PublishedSince::new(since))
+        .await
+        .unwrap();
+
+    assert_eq!(stories.len(), 2);
+    assert!(stories[0].page.is_none());
+    assert_eq!(stories[1].url, server.url("/@me/undated-cccccccccccc"));
+    assert_eq!(stories[1].page.as_ref().unwrap().body(), story);
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn fetched_stories_go_to_the_paragraph_sink() {
+    let json = r#"])}while(1);</x>{"payload":{"value":{"title":"Tips","content":{"bodyModel":{"paragraphs":[{"type":1,"text":"First point"}]}}}}}"#;
+    let server = MockServer::start(vec![MockResponse::new(200, json)]).await;
+    let stored = Arc::new(Mutex::new(vec![]));
+    let sink = stored.clone();
+    let client = MediumClient::new("sid=abc")
+        .unwrap()
+        .with_paragraph_sink(Arc::new(move |url: &str, article: &Article| {
+            sink.lock()
+                .unwrap()
+                .push((url.to_owned(), article.content_hash()));
+        }));
+
+    let article = client
+        .fetch_story(&server.url("/@me/tips-0123456789ab?source=feed"))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        *stored.lock().unwrap(),
+        [(server.url("/@me/tips-0123456789ab"), article.content_hash())]
+    );
+    assert_eq!(article.content_hash().len(), 16);
+}
+
+#[tokio::test]
+async fn fetched_content_goes_to_the_paragraph_sink() {
+    let page =
+        r#"<html><script>{"__typename":"Post","type":"P","text":"First point"}</script></html>"#;
+    let server = MockServer::start(vec![MockResponse::new(200, page)]).await;
+    let stored = Arc::new(Mutex::new(vec![]));
+    let sink = stored.clone();
+    let client = MediumClient::builder("sid=abc")
+        .min_content_chars(0)
+        .build()
+        .unwrap()
+        .with_paragraph_sink(Arc::new(move |url: &str, article: &Article| {
+            sink.lock()
+                .unwrap()
+                .push((url.to_owned(), article.paragraphs.len()));
+        }));
+
+    let content = client
+        .fetch_content(
+            &server.url("/@me/tips-0123456789ab?source=feed"),
+            &ExtractOptions::default(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(content, "First point");
+    assert_eq!(
+        *stored.lock().unwrap(),
+        [(server.url("/@me/tips-0123456789ab"), 1)]
+    );
+}
+
+#[test]
+fn debug_output_redacts_the_cookie() {
+    let builder = MediumClient::builder("sid=very-secret-session");
+    let client = MediumClient::new("sid=very-secret-session").unwrap();
+
+    for debug in [format!("{builder:?}"), format!("{client:?}")] {
+        assert!(!debug.contains("very-secret-session"), "{debug}");
+        assert!(debug.contains("<redacted>"));
+    }
+}
+
+#[tokio::test]
+async fn fetch_responses_keeps_top_direct_responses() {
+    let json = r#"])}while(1);</x>{"payload":{"references":{
+"Post":{
+"r1":{"inResponseToPostId":"0123456789ab","creatorId":"u1","virtuals":{"totalClapCount":3},"previewContent":{"bodyModel":{"paragraphs":[{"text":"Nice"}]}}},
+"r2":{"inResponseToPostId":"0123456789ab","creatorId":"u2","virtuals":{"totalClapCount":40},"previewContent":{"bodyModel":{"paragraphs":[{"text":"Disagree"},{"text":"because"}]}}},
+"r3":{"inResponseToPostId":"r2","virtuals":{"totalClapCount":99},"previewContent":{"bodyModel":{"paragraphs":[{"text":"Reply"}]}}},
+"r4":{"inResponseToPostId":"0123456789ab","virtuals":{"totalClapCount":1},"previewContent":{"bodyModel":{"paragraphs":[{"text":"Late"}]}}}},
+"User":{"u1":{"name":"Ann"},"u2":{"name":"Bo"}}}}}"#;
+    let server = MockServer::start(vec![MockResponse::new(200, json)]).await;
+    let client = MediumClient::new("sid=abc").unwrap();
+
+    let responses = client
+        .fetch_responses(&server.url("/@me/tips-0123456789ab?source=feed"), 2)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        responses,
+        [
+            ArticleResponse {
+                author: Some("Bo".to_owned()),
+                text: "Disagree\nbecause".to_owned(),
+                claps: 40,
+            },
+            ArticleResponse {
+                author: Some("Ann".to_owned()),
+                text: "Nice".to_owned(),
+                claps: 3,
+            },
+        ]
+    );
+    assert_eq!(
+        server.requests()[0].path,
+        "/_/api/posts/0123456789ab/responsesStream"
+    );
+}
+
+#[tokio::test]
+async fn registered_extractor_replaces_medium_one_for_its_host() {
+    let page = r#"<html><article>Notes from my own blog</article>
+<script>{"__typename":"Post","text":"medium text"}</script></html>"#;
+    let server = MockServer::start(vec![MockResponse::new(200, page)]).await;
+    let registry = ExtractorRegistry::new().register("127.0.0.1", |body: &str| {
+        let start = body.find("<article>").ok_or(ClientError::MissMatch)? + "<article>".len();
+        let end = body.find("</article>").ok_or(ClientError::MissMatch)?;
+        Ok(body[start..end].to_owned())
+    });
+    let client = MediumClient::builder("sid=abc")
+        .extractors(registry)
+        .min_content_chars(0)
+        .build()
+        .unwrap();
+    // not a story url, which only other sites' extractors allow
+    let url = server.url("/2024/notes");
+
+    let content = client
+        .fetch_content(&url, &ExtractOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(content, "Notes from my own blog");
+    let data = client.fetch(&url).await.unwrap();
+    assert_eq!(data.content().unwrap(), "Notes from my own blog");
+
+    let client = MediumClient::builder("sid=abc").build().unwrap();
+    assert!(matches!(
+        client.fetch_content(&url, &ExtractOptions::default()).await,
+        Err(ClientError::UnsupportedUrl(_))
+    ));
+    let data = client.fetch(&url).await.unwrap();
+    assert_eq!(data.content().unwrap(), "medium text");
+}
+
+#[tokio::test]
+async fn lists_keep_their_order_and_are_not_nested() {
+    let page = std::fs::read_to_string(
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/lists.html"),
+    )
+    .unwrap();
+    let server = MockServer::start(vec![MockResponse::new(200, page)]).await;
+    let client = MediumClient::new("sid=abc").unwrap();
+    let data = client.fetch(&server.url("/@me/lists")).await.unwrap();
+
+    let content = data.structured_content().unwrap();
+
+    let item = |ordered, text: &str| ListItem {
+        ordered,
+        text: text.to_owned(),
+    };
+    // the bullets the editor shows under the first step are a list of their own
+    assert_eq!(
+        content.lists(),
+        [
+            &[item(true, "Split the crate into a workspace")][..],
+            &[
+                item(false, "Keep shared types in a core crate"),
+                item(false, "Let leaf crates depend on it"),
+            ],
+            &[
+                item(true, "Use a faster linker"),
+                item(true, "Lower the debug info"),
+            ],
+        ]
+    );
+    assert!(matches!(
+        content.blocks.first(),
+        Some(ContentBlock::Paragraph(_))
+    ));
+    assert!(matches!(
+        content.blocks.last(),
+        Some(ContentBlock::Paragraph(_))
+    ));
+    assert_eq!(content.blocks.len(), 5);
+    assert_eq!(
+        content.to_markdown(),
+        "Three ways to speed up a Rust build:\n\n\
+         1. Split the crate into a workspace\n\n\
+         - Keep shared types in a core crate\n\
+         - Let leaf crates depend on it\n\n\
+         1. Use a faster linker\n\
+         2. Lower the debug info\n\n\
+         Measure before and after with cargo build --timings."
+    );
+}
+
+#[test]
+fn digest_email_links_are_unwrapped_in_order() {
+    let html = std::fs::read_to_string(
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/digest.html"),
+    )
+    .unwrap();
+
+    let links = extract_digest_links(&html).unwrap();
+
+    assert_eq!(
+        links,
+        [
+            "https://medium.com/@janedoe/five-rust-tips-0123456789ab",
+            "https://betterprogramming.pub/why-async-rust-7fe9e55da4e1",
+            "https://medium.com/p/a1b2c3d4e5f6",
+        ]
+    );
+}
@@ -0,0 +1,75 @@
+use summary_medium_post::{
+    summary_to_markdown, ArticleMeta, Paragraph, ParagraphKind, StructuredContent,
+};
+
+#[test]
+fn frontmatter_skips_unknown_fields() {
+    let meta = ArticleMeta {
+        title: Some("Rust \"tips\"".to_owned()),
+        url: Some("https://medium.com/@me/rust-tips-0123456789ab".to_owned()),
+        ..ArticleMeta::default()
+    };
+
+    let markdown = summary_to_markdown(&meta, "- one\n- two", Some("claude-3-haiku"));
+
+    assert_eq!(
+        markdown,
+        "---\n\
+         title: \"Rust \\\"tips\\\"\"\n\
+         url: \"https://medium.com/@me/rust-tips-0123456789ab\"\n\
+         model: \"claude-3-haiku\"\n\
+         ---\n\n\
+         # Rust \"tips\"\n\n\
+         - one\n\
+         - two\n"
+    );
+}
+
+#[test]
+fn subtitle_goes_below_the_title() {
+    let meta = ArticleMeta {
+        title: Some("Rust tips".to_owned()),
+        subtitle: Some("Small things that add up".to_owned()),
+        ..ArticleMeta::default()
+    };
+
+    let markdown = summary_to_markdown(&meta, "- one", None);
+
+    assert_eq!(
+        markdown,
+        "---\n\
+         title: \"Rust tips\"\n\
+         subtitle: \"Small things that add up\"\n\
+         ---\n\n\
+         # Rust tips\n\n\
+         > Small things that add up\n\n\
+         - one\n"
+    );
+}
+
+fn text(text: &str) -> Paragraph {
+    Paragraph {
+        kind: ParagraphKind::Text,
+        text: text.to_owned(),
+    }
+}
+
+#[test]
+fn numbered_steps_are_not_footnotes() {
+    let content = StructuredContent::from_paragraphs(vec![
+        text("(1) Install the toolchain."),
+        text("(2) Create a project with cargo new."),
+        text("Lifetimes[1] are checked at compile time."),
+        text("[1] Also called regions."),
+        text("That is all for today."),
+        text("(3) An endnote after the body."),
+    ]);
+
+    assert_eq!(
+        content.footnotes(),
+        [
+            ("[1]".to_owned(), "Also called regions.".to_owned()),
+            ("(3)".to_owned(), "An endnote after the body.".to_owned()),
+        ]
+    );
+}
@@ -0,0 +1,206 @@
+mod common;
+
+use common::{MockResponse, MockServer};
+use summary_medium_post::{
+    build_agent, compare, list_ollama_models, AIResponse, AISummary, AISummaryError, AgentKind,
+    Claude3agent, OpenAIAgent, SummaryConfig, SummaryLength, SummaryStyle, Usage,
+};
+
+#[tokio::test]
+async fn parses_response_without_usage_or_id() {
+    let reply = r#"{"choices":[{"message":{"role":"assistant","content":"- point"}}]}"#;
+    let server = MockServer::start(vec![MockResponse::new(200, reply)]).await;
+    let agent = OpenAIAgent::from_url(&server.url("/v1"), None).unwrap();
+
+    let res = agent.fetch("article".to_owned()).await.unwrap();
+
+    assert_eq!(res.summary().unwrap(), "- point");
+    assert_eq!(res.usage(), None);
+    assert_eq!(res.model_used(), "");
+}
+
+#[tokio::test]
+async fn sends_chat_completion_to_base_url() {
+    let reply = r#"{"id":"x","model":"llama-3.1-8b","choices":[{"message":{"content":"ok"},"finish_reason":"stop"}],"usage":{"prompt_tokens":12,"completion_tokens":3,"total_tokens":15}}"#;
+    let server = MockServer::start(vec![MockResponse::new(200, reply)]).await;
+    let agent = OpenAIAgent::from_url(&server.url("/openai/v1/"), Some("key"))
+        .unwrap()
+        .with_model("llama-3.1-8b");
+
+    let res = agent.fetch("article".to_owned()).await.unwrap();
+
+    assert_eq!(res.model_used(), "llama-3.1-8b");
+    assert_eq!(
+        res.usage(),
+        Some(Usage {
+            input_tokens: 12,
+            output_tokens: 3,
+            ..Usage::default()
+        })
+    );
+    let request = &server.requests()[0];
+    assert_eq!(request.path, "/openai/v1/chat/completions");
+    assert_eq!(request.header("authorization"), Some("Bearer key"));
+    let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+    assert_eq!(body["model"], "llama-3.1-8b");
+    assert_eq!(body["messages"][0]["role"], "system");
+    assert_eq!(body["messages"][1]["content"], "article");
+}
+
+#[tokio::test]
+async fn ollama_agent_uses_the_openai_compatible_api() {
+    let reply = r#"{"model":"llama3","choices":[{"message":{"content":"- point"}}]}"#;
+    let server = MockServer::start(vec![MockResponse::new(200, reply)]).await;
+    // no other test in this file reads OLLAMA_URL or OLLAMA_MODEL
+    std::env::set_var("OLLAMA_URL", server.url("/"));
+    std::env::remove_var("OLLAMA_MODEL");
+
+    let agent = build_agent(AgentKind::Ollama).unwrap();
+
+    assert_eq!(
+        agent.summarize("article".to_owned()).await.unwrap(),
+        "- point"
+    );
+    let request = &server.requests()[0];
+    assert_eq!(request.path, "/v1/chat/completions");
+    assert_eq!(request.header("authorization"), None);
+    let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+    assert_eq!(body["model"], "llama3");
+}
+
+#[tokio::test]
+async fn summary_pointer_reads_custom_response_shapes() {
+    let reply = r#"{"model":"local","result":{"text":"- point","tokens":7}}"#;
+    let server = MockServer::start(vec![MockResponse::new(200, reply)]).await;
+    let agent = OpenAIAgent::from_url(&server.url("/v1"), None)
+        .unwrap()
+        .with_summary_pointer("/result/text")
+        .unwrap();
+
+    let res = agent.fetch("article".to_owned()).await.unwrap();
+    assert_eq!(res.summary().unwrap(), "- point");
+    assert_eq!(res.model_used(), "local");
+
+    let agent = agent.with_summary_pointer("/result/tokens").unwrap();
+    assert!(matches!(
+        agent.fetch("article".to_owned()).await,
+        Err(AISummaryError::SummaryNotFound { pointer, .. }) if pointer == "/result/tokens"
+    ));
+    assert!(matches!(
+        agent.with_summary_pointer("result/text"),
+        Err(AISummaryError::InvalidPointer(_))
+    ));
+}
+
+#[tokio::test]
+async fn empty_choices_is_empty_response() {
+    let server = MockServer::start(vec![MockResponse::new(200, r#"{"choices":[]}"#)]).await;
+    let agent = OpenAIAgent::from_url(&server.url("/v1"), None).unwrap();
+
+    let res = agent.fetch("article".to_owned()).await.unwrap();
+
+    assert!(res.summary().is_err());
+}
+
+#[test]
+fn config_carries_over_between_agents() {
+    let claude = Claude3agent::from_url("http://localhost/v1/messages", "key")
+        .unwrap()
+        .with_model("claude-3-5-sonnet-20240620")
+        .with_length(SummaryLength::Short)
+        .with_style(SummaryStyle::TlDr)
+        .with_temperature(0.2)
+        .unwrap();
+    let openai = OpenAIAgent::from_url("http://localhost/v1", None)
+        .unwrap()
+        .with_model("llama-3.1-8b")
+        .with_config(claude.config().clone())
+        .unwrap();
+
+    let claude_body = claude.build_body("article".to_owned());
+    let body = openai.build_body("article".to_owned());
+
+    // each agent keeps its own model
+    assert_eq!(claude_body["model"], "claude-3-5-sonnet-20240620");
+    assert_eq!(body["model"], "llama-3.1-8b");
+    assert_eq!(body["max_tokens"], 256);
+    assert_eq!(body["temperature"], claude_body["temperature"]);
+    assert_eq!(body["messages"][0]["content"], claude_body["system"]);
+}
+
+#[test]
+fn invalid_config_is_rejected() {
+    let config = SummaryConfig {
+        top_p: Some(2.0),
+        ..SummaryConfig::default()
+    };
+    assert!(matches!(
+        OpenAIAgent::from_url("http://localhost/v1", None)
+            .unwrap()
+            .with_config(config),
+        Err(AISummaryError::InvalidSampling { name: "top_p", .. })
+    ));
+}
+
+#[tokio::test]
+async fn lists_models_of_openai_and_ollama() {
+    let server = MockServer::start(vec![
+        MockResponse::new(
+            200,
+            r#"{"object":"list","data":[{"id":"gpt-4o"},{"id":"gpt-4o-mini"}]}"#,
+        ),
+        MockResponse::new(
+            200,
+            r#"{"models":[{"name":"mistral:latest"},{"name":"llama3:8b"}]}"#,
+        ),
+    ])
+    .await;
+    let agent = OpenAIAgent::from_url(&server.url("/v1"), Some("key")).unwrap();
+
+    assert_eq!(
+        agent.list_models().await.unwrap(),
+        ["gpt-4o", "gpt-4o-mini"]
+    );
+    assert_eq!(
+        list_ollama_models(&server.url("/")).await.unwrap(),
+        ["llama3:8b", "mistral:latest"]
+    );
+    let requests = server.requests();
+    assert_eq!(requests[0].path, "/v1/models");
+    assert_eq!(requests[0].header("authorization"), Some("Bearer key"));
+    assert_eq!(requests[1].path, "/api/tags");
+}
+
+#[tokio::test]
+async fn compare_runs_both_agents_on_the_same_content() {
+    let claude_reply = r#"{"id":"msg","type":"message","role":"assistant","model":"claude-3-haiku-20240307","content":[{"type":"text","text":"- from claude"}],"stop_reason":"end_turn","usage":{"input_tokens":20,"output_tokens":4}}"#;
+    let claude_server = MockServer::start(vec![MockResponse::new(200, claude_reply)]).await;
+    let openai_server = MockServer::start(vec![MockResponse::new(404, "no such model")]).await;
+    let claude = Claude3agent::from_url(claude_server.url("/v1/messages"), "key").unwrap();
+    let openai = OpenAIAgent::from_url(&openai_server.url("/v1"), None).unwrap();
+
+    let comparison = compare(&claude, &openai, "article").await;
+
+    assert_eq!(comparison.first.summary.unwrap(), "- from claude");
+    assert_eq!(
+        comparison.first.model.as_deref(),
+        Some("claude-3-haiku-20240307")
+    );
+    assert_eq!(comparison.first.usage.unwrap().output_tokens, 4);
+    assert!(matches!(
+        comparison.second.summary,
+        Err(AISummaryError::ApiError { status: 404, .. })
+    ));
+    assert_eq!(comparison.second.model, None);
+    let sent: serde_json::Value = serde_json::from_str(&openai_server.requests()[0].body).unwrap();
+    assert_eq!(sent["messages"][1]["content"], "article");
+}
+
+#[test]
+fn debug_output_hides_api_keys() {
+    let claude = Claude3agent::from_url("http://localhost/v1/messages", "sk-ant-secret").unwrap();
+    let openai = OpenAIAgent::from_url("http://localhost/v1", Some("sk-openai-secret")).unwrap();
+
+    assert!(!format!("{claude:?}").contains("sk-ant-secret"));
+    assert!(!format!("{openai:?}").contains("sk-openai-secret"));
+}
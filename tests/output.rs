@@ -0,0 +1,153 @@
+use std::fs;
+
+use summary_medium_post::{
+    clean_url, estimate_cost, slugify, summary_file_stem, write_output, Claude3respose, CostTotal,
+    LineEnding, ModelPrice, OutputEncoding, PriceTable, SummaryFormat, SummaryOutput, Usage,
+};
+
+#[test]
+fn slugify_keeps_lowercase_words() {
+    assert_eq!(
+        slugify("Rust's Async Book: Part 2!"),
+        "rust-s-async-book-part-2"
+    );
+    assert_eq!(slugify("  ---  "), "");
+    assert!(slugify(&"word ".repeat(40)).len() < 80);
+}
+
+#[test]
+fn clean_url_strips_tracking_and_keeps_friend_token() {
+    assert_eq!(
+        clean_url("https://medium.com/p/0123456789ab?utm_source=x&sk=f00d&gi=1a2b&fbclid=c3"),
+        "https://medium.com/p/0123456789ab?sk=f00d"
+    );
+    assert_eq!(
+        clean_url("https://medium.com/@me/tips-0123456789ab?source=rss&ref=home"),
+        "https://medium.com/@me/tips-0123456789ab"
+    );
+    assert_eq!(clean_url("not a url"), "not a url");
+}
+
+#[test]
+fn stored_summary_url_is_cleaned() {
+    let response: Claude3respose = serde_json::from_str(
+        r#"{"id":"msg","type":"message","role":"assistant","model":"claude-3-haiku-20240307","content":[{"type":"text","text":"- ok"}],"stop_reason":"end_turn","usage":{"input_tokens":8,"output_tokens":2}}"#,
+    )
+    .unwrap();
+
+    let output = SummaryOutput::from_response("article", &response)
+        .unwrap()
+        .with_url("https://medium.com/p/0123456789ab?source=friends_link&sk=f00d");
+
+    assert_eq!(
+        output.url.as_deref(),
+        Some("https://medium.com/p/0123456789ab?sk=f00d")
+    );
+}
+
+#[test]
+fn same_titles_get_different_file_names() {
+    assert_eq!(
+        summary_file_stem("Rust Tips", "https://medium.com/@a/rust-tips-0123456789ab"),
+        "rust-tips-0123456789ab"
+    );
+    assert_eq!(
+        summary_file_stem("Rust Tips", "https://medium.com/@b/rust-tips-7fe9e55da4e1"),
+        "rust-tips-7fe9e55da4e1"
+    );
+    assert_eq!(
+        summary_file_stem("", "https://medium.com/p/0123456789ab"),
+        "0123456789ab"
+    );
+    assert_eq!(summary_file_stem("Notes", "stdin"), "notes");
+    assert_eq!(summary_file_stem("", "stdin"), "stdin");
+    assert_eq!(summary_file_stem("", ""), "summary");
+}
+
+#[test]
+fn write_output_creates_parent_directories() {
+    let dir = std::env::temp_dir().join(format!("summary-output-{}", std::process::id()));
+    let path = dir.join("nested/summary.txt");
+
+    write_output(&path, "- point\n", &OutputEncoding::default()).unwrap();
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), "- point\n");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn windows_encoding_adds_bom_and_crlf() {
+    let dir = std::env::temp_dir().join(format!("summary-encoding-{}", std::process::id()));
+    let path = dir.join("summary.txt");
+    let encoding = OutputEncoding {
+        bom: true,
+        line_ending: LineEnding::CrLf,
+    };
+
+    write_output(&path, "- one\n- two\r\n", &encoding).unwrap();
+
+    assert_eq!(fs::read(&path).unwrap(), b"\xEF\xBB\xBF- one\r\n- two\r\n");
+    assert_eq!(OutputEncoding::default().encode("- one\r\n"), b"- one\n");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn cost_uses_the_longest_priced_prefix() {
+    let usage = Usage {
+        input_tokens: 1_000_000,
+        output_tokens: 200_000,
+        ..Usage::default()
+    };
+
+    assert_eq!(estimate_cost(&usage, "claude-3-haiku-20240307"), Some(0.5));
+    assert_eq!(estimate_cost(&usage, "gpt-4o-mini-2024-07-18"), Some(0.27));
+    assert_eq!(estimate_cost(&usage, "llama3"), None);
+
+    let mut prices = PriceTable::default();
+    prices.set(
+        "llama3",
+        ModelPrice {
+            input: 0.0,
+            output: 0.0,
+        },
+    );
+    assert_eq!(prices.estimate(&usage, "llama3:8b"), Some(0.0));
+}
+
+#[test]
+fn cost_total_counts_unpriced_summaries() {
+    let mut total = CostTotal::default();
+    total.add(Some(0.25));
+    total.add(None);
+    total.add(Some(0.5));
+
+    assert_eq!(
+        total,
+        CostTotal {
+            usd: 0.75,
+            summaries: 3,
+            unpriced: 1,
+        }
+    );
+}
+
+#[test]
+fn summary_format_reshapes_bullets() {
+    let summary = "Here is a summary:\n- First point\n  - detail\n* Second point";
+
+    assert_eq!(SummaryFormat::AsIs.apply(summary), summary);
+    assert_eq!(
+        SummaryFormat::Numbered.apply(summary),
+        "1. First point\n     - detail\n2. Second point"
+    );
+    assert_eq!(
+        SummaryFormat::JsonArray.apply(summary),
+        r#"["First point\n  - detail","Second point"]"#
+    );
+    assert_eq!(SummaryFormat::Plain.apply("1. One\n2. Two"), "One\nTwo");
+    assert_eq!(
+        "json".parse::<SummaryFormat>().unwrap(),
+        SummaryFormat::JsonArray
+    );
+    assert!("yaml".parse::<SummaryFormat>().is_err());
+}
@@ -0,0 +1,173 @@
+mod common;
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use common::{MockResponse, MockServer};
+use summary_medium_post::{
+    summarize_url_to_markdown_file_with_client, summarize_with_client, AISummaryError,
+    CircuitBreaker, ClientError, DynSummarizer, Error, FallbackAgent, MediumClient, SummaryFuture,
+};
+
+struct Echo;
+
+impl DynSummarizer for Echo {
+    fn summarize(&self, content: String) -> SummaryFuture<'_> {
+        Box::pin(async move { Ok(format!("echo: {content}")) })
+    }
+}
+
+struct Failing;
+
+impl DynSummarizer for Failing {
+    fn summarize(&self, _content: String) -> SummaryFuture<'_> {
+        Box::pin(async { Err(AISummaryError::EmptyResponse) })
+    }
+}
+
+#[tokio::test]
+async fn heterogeneous_agents_in_one_vec() {
+    let agents: Vec<Box<dyn DynSummarizer>> =
+        vec![Box::new(Echo), Box::new(Arc::new(Failing)), Box::new(&Echo)];
+
+    let mut results = vec![];
+    for agent in &agents {
+        results.push(agent.summarize("text".to_owned()).await.is_ok());
+    }
+
+    assert_eq!(results, [true, false, true]);
+}
+
+#[tokio::test]
+async fn fallback_uses_first_success() {
+    let agent = FallbackAgent::new(vec![Box::new(Failing)]).then(Echo);
+
+    let summary = agent.summarize("text".to_owned()).await.unwrap();
+
+    assert_eq!(summary, "echo: text");
+}
+
+#[tokio::test]
+async fn fallback_returns_last_error() {
+    let agent = FallbackAgent::new(vec![]);
+    assert!(matches!(
+        agent.summarize("text".to_owned()).await,
+        Err(AISummaryError::NoAgents)
+    ));
+
+    let agent = agent.then(Failing);
+    assert!(matches!(
+        agent.summarize("text".to_owned()).await,
+        Err(AISummaryError::EmptyResponse)
+    ));
+}
+
+/// Fails the first `failures` calls.
+struct Flaky {
+    failures: usize,
+    calls: AtomicUsize,
+}
+
+impl DynSummarizer for Flaky {
+    fn summarize(&self, content: String) -> SummaryFuture<'_> {
+        let call = self.calls.fetch_add(1, Ordering::SeqCst);
+        Box::pin(async move {
+            match call < self.failures {
+                true => Err(AISummaryError::EmptyResponse),
+                false => Ok(content),
+            }
+        })
+    }
+}
+
+#[tokio::test]
+async fn circuit_breaker_fails_fast_after_consecutive_failures() {
+    let flaky = Flaky {
+        failures: 3,
+        calls: AtomicUsize::new(0),
+    };
+    let breaker = CircuitBreaker::new(&flaky).threshold(2).cooldown(None);
+
+    for _ in 0..2 {
+        assert!(matches!(
+            breaker.summarize("text".to_owned()).await,
+            Err(AISummaryError::EmptyResponse)
+        ));
+    }
+    assert!(breaker.is_open());
+    assert!(matches!(
+        breaker.summarize("text".to_owned()).await,
+        Err(AISummaryError::CircuitOpen {
+            failures: 2,
+            retry_in: None
+        })
+    ));
+    assert_eq!(flaky.calls.load(Ordering::SeqCst), 2);
+
+    let breaker = breaker.cooldown(Some(Duration::ZERO));
+    // the probe after the cooldown fails and opens it again, the next one succeeds
+    assert!(breaker.summarize("text".to_owned()).await.is_err());
+    assert_eq!(breaker.summarize("text".to_owned()).await.unwrap(), "text");
+    assert!(!breaker.is_open());
+}
+
+#[tokio::test]
+async fn summarize_reports_either_side_as_one_error() {
+    let page = r#"<html><script>{"__typename":"Post","type":"P","text":"Hello"}</script></html>"#;
+    let server = MockServer::start(vec![
+        MockResponse::new(200, page),
+        MockResponse::new(200, page),
+        MockResponse::new(404, "gone"),
+    ])
+    .await;
+    let client = MediumClient::builder("sid=abc")
+        .min_content_chars(0)
+        .build()
+        .unwrap();
+
+    let summary =
+        summarize_with_client(&client, &server.url("/@me/post-0123456789ab"), &Echo).await;
+    assert_eq!(summary.unwrap(), "echo: Hello");
+
+    let err = summarize_with_client(&client, &server.url("/@me/post-0123456789ab"), &Failing)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::Summary(AISummaryError::EmptyResponse)));
+
+    let err = summarize_with_client(&client, &server.url("/@me/gone-0123456789ab"), &Echo)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        Error::Client(ClientError::BadStatus { status: 404, .. })
+    ));
+}
+
+#[tokio::test]
+async fn markdown_file_is_named_after_the_title() {
+    let page = r#"<html><head><meta property="og:title" content="Rust Tips"></head>
+<script>{"__typename":"Post","type":"P","text":"Hello"}</script></html>"#;
+    let server = MockServer::start(vec![MockResponse::new(200, page)]).await;
+    let client = MediumClient::builder("sid=abc")
+        .min_content_chars(0)
+        .build()
+        .unwrap();
+    let dir = std::env::temp_dir().join(format!("summary-notes-{}", std::process::id()));
+    let url = server.url("/@me/rust-tips-0123456789ab?source=rss");
+
+    let path = summarize_url_to_markdown_file_with_client(&client, &url, &Echo, dir.join("notes"))
+        .await
+        .unwrap();
+
+    assert_eq!(path, dir.join("notes/rust-tips-0123456789ab.md"));
+    let markdown = std::fs::read_to_string(&path).unwrap();
+    assert!(markdown.starts_with("---\ntitle: \"Rust Tips\"\nurl: "));
+    assert!(markdown.contains(&server.url("/@me/rust-tips-0123456789ab\"")));
+    assert!(markdown.ends_with("# Rust Tips\n\n- echo: Hello\n"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,78 @@
+use summary_medium_post::{
+    parse_bullets, truncate_content, truncate_content_with, truncate_text_with, TruncateMarkers,
+};
+
+#[test]
+fn cuts_at_the_last_sentence_that_fits() {
+    let text = "First point. Second point! Third point goes past the limit.";
+
+    assert_eq!(
+        truncate_content(text, 30),
+        "First point. Second point! [truncated]"
+    );
+    assert_eq!(truncate_content(text, 100), text);
+}
+
+#[test]
+fn abbreviations_are_not_sentence_ends() {
+    let text = "Ask Dr. Smith, e.g. about it. Then go.";
+
+    assert_eq!(
+        truncate_content_with(text, 33, &TruncateMarkers::new("…", "")),
+        "Ask Dr. Smith, e.g. about it."
+    );
+    assert_eq!(
+        truncate_content_with(text, 20, &TruncateMarkers::new("…", "")),
+        "Ask Dr. Smith, e.g.…"
+    );
+}
+
+#[test]
+fn text_without_a_boundary_is_cut_mid_sentence() {
+    assert_eq!(
+        truncate_content("one long run on", 8),
+        "one long... [truncated]"
+    );
+    // a period at the limit still counts when a space follows it
+    assert_eq!(truncate_content("Short. More", 6), "Short. [truncated]");
+}
+
+#[test]
+fn custom_markers_go_at_the_cut() {
+    let markers = TruncateMarkers::new("…", " (read more)");
+
+    assert_eq!(
+        truncate_text_with("Borrowing rules explained", 9, &markers),
+        "Borrowing… (read more)"
+    );
+    assert_eq!(
+        truncate_content_with("Short one. Then a much longer one.", 15, &markers),
+        "Short one. (read more)"
+    );
+    assert_eq!(truncate_text_with("fits", 9, &markers), "fits");
+}
+
+#[test]
+fn bullets_skip_the_preamble_and_keep_sub_bullets_with_their_parent() {
+    let text = "Here is a summary:\n\n1. Rust is fast\n   - no GC\n   * zero-cost abstractions\n2) Async works\nacross many lines\n\u{2022} Tooling is good\n";
+
+    assert_eq!(
+        parse_bullets(text),
+        vec![
+            "Rust is fast\n   - no GC\n   * zero-cost abstractions",
+            "Async works across many lines",
+            "Tooling is good",
+        ]
+    );
+}
+
+#[test]
+fn text_without_bullets_is_one_item() {
+    assert_eq!(
+        parse_bullets("  Just one sentence.\n"),
+        vec!["Just one sentence."]
+    );
+    assert!(parse_bullets(" \n").is_empty());
+    // a number without its separator is not a marker
+    assert_eq!(parse_bullets("2024 was busy"), vec!["2024 was busy"]);
+}
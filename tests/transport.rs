@@ -0,0 +1,162 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use summary_medium_post::{
+    AISummary, AISummaryError, Claude3agent, ClientError, HttpRequest, HttpResponse, HttpTransport,
+    OpenAIAgent, RetryPolicy, TransportFuture, RESPONSE_PREVIEW_CHARS,
+};
+
+/// Replies with queued responses and records the requests, no network involved.
+#[derive(Default)]
+struct Canned {
+    responses: Mutex<VecDeque<HttpResponse>>,
+    requests: Mutex<Vec<HttpRequest>>,
+}
+
+impl Canned {
+    fn new(responses: Vec<HttpResponse>) -> Arc<Self> {
+        Arc::new(Self {
+            responses: Mutex::new(responses.into()),
+            requests: Mutex::default(),
+        })
+    }
+
+    fn requests(&self) -> Vec<HttpRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl HttpTransport for Canned {
+    fn post(&self, request: HttpRequest) -> TransportFuture<'_> {
+        self.requests.lock().unwrap().push(request);
+        let response = self.responses.lock().unwrap().pop_front();
+        Box::pin(async move { response.ok_or(ClientError::MissMatch) })
+    }
+}
+
+const REPLY: &str = r#"{"id":"msg","type":"message","role":"assistant","model":"claude-3-haiku-20240307","content":[{"type":"text","text":"- ok"}],"stop_reason":"end_turn","usage":{"input_tokens":8,"output_tokens":2}}"#;
+
+fn agent(transport: Arc<Canned>) -> Claude3agent {
+    Claude3agent::from_url("http://claude.invalid/v1/messages", "key")
+        .unwrap()
+        .with_transport(transport)
+}
+
+#[tokio::test]
+async fn sends_body_through_transport() {
+    let transport = Canned::new(vec![HttpResponse::new(200, REPLY)]);
+    let agent = agent(transport.clone());
+
+    let res = agent.fetch("article".to_owned()).await.unwrap();
+
+    assert_eq!(res.summary().unwrap(), "- ok");
+    let request = &transport.requests()[0];
+    assert_eq!(request.url, "http://claude.invalid/v1/messages");
+    assert_eq!(request.headers["x-api-key"], "key");
+    let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+    assert_eq!(body, agent.build_body("article".to_owned()));
+}
+
+#[tokio::test]
+async fn retries_rate_limit_then_succeeds() {
+    let transport = Canned::new(vec![
+        HttpResponse::new(
+            429,
+            r#"{"type":"error","error":{"type":"rate_limit_error","message":"slow down"}}"#,
+        ),
+        HttpResponse::new(200, REPLY),
+    ]);
+    let agent = agent(transport.clone())
+        .with_retry(RetryPolicy::new(1).initial_backoff(Duration::from_millis(1)));
+
+    agent.fetch("article".to_owned()).await.unwrap();
+
+    assert_eq!(transport.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn maps_error_status_and_bad_json() {
+    let transport = Canned::new(vec![HttpResponse::new(
+        400,
+        r#"{"type":"error","error":{"type":"invalid_request_error","message":"max_tokens: too large"}}"#,
+    )]);
+    let err = agent(transport)
+        .fetch("article".to_owned())
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, AISummaryError::ApiError { status: 400, ref message, .. }
+        if message == "invalid_request_error: max_tokens: too large")
+    );
+
+    let transport = Canned::new(vec![HttpResponse::new(200, "<html>gateway</html>")]);
+    let err = agent(transport)
+        .fetch("article".to_owned())
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        AISummaryError::InvalidResponse { ref body, .. } if body == "<html>gateway</html>"
+    ));
+
+    let transport = Canned::new(vec![HttpResponse::new(200, "x".repeat(5000))]);
+    let err = agent(transport)
+        .fetch("article".to_owned())
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        AISummaryError::InvalidResponse { ref body, .. }
+        if body.starts_with(&"x".repeat(RESPONSE_PREVIEW_CHARS)) && body.len() < 400
+    ));
+}
+
+#[tokio::test]
+async fn openai_agent_uses_transport_too() {
+    let transport = Canned::new(vec![HttpResponse::new(
+        200,
+        r#"{"choices":[{"message":{"content":"- ok"}}]}"#,
+    )]);
+    let agent = OpenAIAgent::from_url("http://openai.invalid/v1", None)
+        .unwrap()
+        .with_transport(transport.clone());
+
+    let res = agent.fetch("article".to_owned()).await.unwrap();
+
+    assert_eq!(res.summary().unwrap(), "- ok");
+    assert_eq!(
+        transport.requests()[0].url,
+        "http://openai.invalid/v1/chat/completions"
+    );
+}
+
+#[tokio::test]
+async fn beta_features_go_in_one_header() {
+    let transport = Canned::new(vec![HttpResponse::new(200, REPLY)]);
+    agent(transport.clone())
+        .fetch("article".to_owned())
+        .await
+        .unwrap();
+    assert!(!transport.requests()[0]
+        .headers
+        .contains_key("anthropic-beta"));
+
+    let transport = Canned::new(vec![HttpResponse::new(200, REPLY)]);
+    agent(transport.clone())
+        .with_betas(vec![
+            "output-128k-2025-02-19".to_owned(),
+            " ".to_owned(),
+            "token-efficient-tools-2025-02-19".to_owned(),
+        ])
+        .unwrap()
+        .fetch("article".to_owned())
+        .await
+        .unwrap();
+    assert_eq!(
+        transport.requests()[0].headers["anthropic-beta"],
+        "output-128k-2025-02-19,token-efficient-tools-2025-02-19"
+    );
+}